@@ -0,0 +1,147 @@
+use std::fmt;
+
+/// How much the split of an address can be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// the address has a street, city, postal code and a 2-letter country code
+    High,
+    /// some components are missing or does not look like what they are supposed to be
+    Low,
+}
+
+/// An address of the export split into its components.
+///
+/// The addresses are written as `street, city, postal code, country`,
+/// the street itself can contain commas, so the components are taken from the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub street: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub confidence: Confidence,
+}
+
+impl Address {
+    pub fn parse(raw: &str) -> Self {
+        // the components are taken from the end, so the street keeps its own commas
+        let mut parts: Vec<&str> = raw.rsplitn(4, ',').map(str::trim).collect();
+        parts.reverse();
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+
+        match parts.as_slice() {
+            [street, city, postal_code, country] => {
+                let country_code = normalize_country(country);
+                let confidence = if !street.is_empty()
+                    && !city.is_empty()
+                    && is_postal_code(postal_code)
+                    && country_code.is_some()
+                {
+                    Confidence::High
+                } else {
+                    Confidence::Low
+                };
+                Address {
+                    street: non_empty(street),
+                    city: non_empty(city),
+                    postal_code: non_empty(postal_code),
+                    country: country_code.or_else(|| non_empty(country)),
+                    confidence,
+                }
+            }
+            // either `city, postal code, country` or `street, city, country`
+            [first, second, country] => {
+                let (street, city, postal_code) = if is_postal_code(second) {
+                    (None, non_empty(first), non_empty(second))
+                } else {
+                    (non_empty(first), non_empty(second), None)
+                };
+                Address {
+                    street,
+                    city,
+                    postal_code,
+                    country: normalize_country(country).or_else(|| non_empty(country)),
+                    confidence: Confidence::Low,
+                }
+            }
+            _ => Address {
+                street: non_empty(raw.trim()),
+                city: None,
+                postal_code: None,
+                country: None,
+                confidence: Confidence::Low,
+            },
+        }
+    }
+}
+
+/// a 2-letter country code, in upper case
+fn normalize_country(country: &str) -> Option<String> {
+    (country.len() == 2 && country.chars().all(|c| c.is_ascii_alphabetic()))
+        .then(|| country.to_ascii_uppercase())
+}
+
+/// Postal codes differ per country, eg: `12352`, `7339JB`, `00-950`, `SW1A 1AA`,
+/// but they are short and always have a digit.
+fn is_postal_code(postal_code: &str) -> bool {
+    (3..=10).contains(&postal_code.len())
+        && postal_code.chars().any(|c| c.is_ascii_digit())
+        && postal_code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-')
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Confidence::High => write!(f, "high"),
+            Confidence::Low => write!(f, "low"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(street: &str, city: &str, postal_code: &str, country: &str, confidence: Confidence) -> Address {
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        Address {
+            street: non_empty(street),
+            city: non_empty(city),
+            postal_code: non_empty(postal_code),
+            country: non_empty(country),
+            confidence,
+        }
+    }
+
+    #[test]
+    fn parse_addresses() {
+        assert_eq!(
+            Address::parse("Valéestrasse 121, Cracow, 55042, PL"),
+            address("Valéestrasse 121", "Cracow", "55042", "PL", Confidence::High)
+        );
+        assert_eq!(
+            Address::parse("de Parkelaar 32, Ugchelen, 7339JB, NL"),
+            address("de Parkelaar 32", "Ugchelen", "7339JB", "NL", Confidence::High)
+        );
+        // the street has commas of its own
+        assert_eq!(
+            Address::parse("Str. Vulturilor 75,Ap 2,Et 1, Bucharest, 030853, RO"),
+            address("Str. Vulturilor 75,Ap 2,Et 1", "Bucharest", "030853", "RO", Confidence::High)
+        );
+        assert_eq!(
+            Address::parse("Cracow, 55042, pl"),
+            address("", "Cracow", "55042", "PL", Confidence::Low)
+        );
+        assert_eq!(
+            Address::parse("somewhere, Poland"),
+            address("somewhere, Poland", "", "", "", Confidence::Low)
+        );
+        assert_eq!(
+            Address::parse("Main street 1, Springfield, unknown, Poland"),
+            address("Main street 1", "Springfield", "unknown", "Poland", Confidence::Low)
+        );
+        assert_eq!(Address::parse("").confidence, Confidence::Low);
+    }
+}
@@ -0,0 +1,45 @@
+//! Compares the 2 ways of handing the parsed records to `DataSource::from_csv`: writing them
+//! into the buffer behind the type header, which is what the loader does, against the former
+//! approach of writing them into a csv string, then copying it behind the header.
+//! Both parse the same export and write the same columns.
+//!
+//! Run with `cargo bench -p customer`.
+use criterion::{criterion_group, criterion_main, Criterion};
+use flate2::bufread::GzDecoder;
+use gauntlet::DataSource;
+use std::io::Read;
+use customer::{Customer, LoadOptions};
+
+static DATA: &[u8] = include_bytes!("../../../data/customer_export.gz");
+
+fn parse(csv: &[u8]) -> Vec<Customer> {
+    customer::customers(csv).collect::<Result<_, _>>().unwrap()
+}
+
+/// the table written behind the header, in the buffer handed to gauntlet
+fn single_buffer(csv: &[u8], options: &LoadOptions) -> DataSource {
+    customer::customer_table(&parse(csv), options).unwrap()
+}
+
+/// the same table written to a string, then copied behind the header
+fn string_copy(csv: &[u8], options: &LoadOptions) -> DataSource {
+    let table = String::from_utf8(customer::customer_table_csv(&parse(csv), options).unwrap()).unwrap();
+    let (header, body) = table.split_once('\n').unwrap();
+    let data = format!("{header}\n{body}");
+    DataSource::from_csv(data.into_bytes()).unwrap()
+}
+
+fn load(c: &mut Criterion) {
+    let mut csv = vec![];
+    GzDecoder::new(DATA).read_to_end(&mut csv).unwrap();
+    let options = LoadOptions::default();
+
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    group.bench_function("single_buffer", |b| b.iter(|| single_buffer(&csv, &options)));
+    group.bench_function("string_copy", |b| b.iter(|| string_copy(&csv, &options)));
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
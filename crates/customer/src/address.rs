@@ -0,0 +1,64 @@
+use ::address::Address;
+use crate::schema::table;
+use crate::Customer;
+use crate::Masking;
+
+impl Customer {
+    pub fn invoice_address_parts(&self) -> Address {
+        Address::parse(&self.invoice_address)
+    }
+
+    pub fn shipping_address_parts(&self) -> Address {
+        Address::parse(&self.shipping_address)
+    }
+}
+
+table! {
+    /// The address components added to the customer table
+    pub(crate) struct AddressColumns {
+        invoice_street: Option<String>,
+        invoice_city: Option<String>,
+        invoice_postal_code: Option<String>,
+        invoice_country: Option<String>,
+        invoice_address_confidence: String,
+        shipping_street: Option<String>,
+        shipping_city: Option<String>,
+        shipping_postal_code: Option<String>,
+        shipping_country: Option<String>,
+        shipping_address_confidence: String,
+    }
+}
+
+impl From<&Customer> for AddressColumns {
+    fn from(customer: &Customer) -> Self {
+        let invoice = customer.invoice_address_parts();
+        let shipping = customer.shipping_address_parts();
+        AddressColumns {
+            invoice_street: invoice.street,
+            invoice_city: invoice.city,
+            invoice_postal_code: invoice.postal_code,
+            invoice_country: invoice.country,
+            invoice_address_confidence: invoice.confidence.to_string(),
+            shipping_street: shipping.street,
+            shipping_city: shipping.city,
+            shipping_postal_code: shipping.postal_code,
+            shipping_country: shipping.country,
+            shipping_address_confidence: shipping.confidence.to_string(),
+        }
+    }
+}
+
+impl AddressColumns {
+    /// mask the street and postal code the same way as in the masked address
+    pub(crate) fn masked(self, masking: &Masking) -> Self {
+        let street = |street: Option<String>| street.map(|street| masking.token("street", &street));
+        let postal = |postal_code: Option<String>| postal_code.map(|postal_code| masking.token("postal", &postal_code));
+        AddressColumns {
+            invoice_street: street(self.invoice_street),
+            invoice_postal_code: postal(self.invoice_postal_code),
+            shipping_street: street(self.shipping_street),
+            shipping_postal_code: postal(self.shipping_postal_code),
+            ..self
+        }
+    }
+}
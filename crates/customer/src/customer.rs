@@ -1,108 +1,611 @@
 use std::fmt::Debug;
 use flate2::bufread::GzDecoder;
 use std::io::BufReader;
+use std::io::BufRead;
+use std::io::Read;
+use std::fs::File;
+use std::path::Path;
 use serde::{Deserialize, Serialize};
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use gauntlet::DataSource;
-use std::io::Cursor;
+use csv::StringRecord;
+use crate::address::AddressColumns;
+use crate::phone::PhoneColumns;
+use crate::schema::{table, Column, Record, Table, TableWriter};
 use crate::Error;
+use crate::Masking;
+use crate::RowError;
 
+#[cfg(feature = "embedded")]
 static DATA: &[u8]  = include_bytes!("../../../data/customer_export.gz");
 
+/// the first 2 bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Customer{
-    eq_id: Option<u64>,
-    sponsor_eq_id: Option<u64>,
-    parent_eq_id: Option<u64>,
-    created_date: Option<NaiveDateTime>,
-    change_date: Option<NaiveDateTime>,
-    full_name: String,
-    invoice_phone_number: String,
-    delivery_phone_number: String,
-    invoice_address: String,
-    shipping_address: String,
+
+table! {
+    /// A customer record of the export
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Customer{
+        pub(crate) eq_id: Option<u64>,
+        pub(crate) sponsor_eq_id: Option<u64>,
+        pub(crate) parent_eq_id: Option<u64>,
+        pub(crate) created_date: Option<DateTime<Utc>>,
+        pub(crate) change_date: Option<DateTime<Utc>>,
+        pub(crate) full_name: String,
+        pub(crate) invoice_phone_number: String,
+        pub(crate) delivery_phone_number: String,
+        pub(crate) invoice_address: String,
+        pub(crate) shipping_address: String,
+    }
+}
+
+
+impl Customer {
+    pub fn eq_id(&self) -> Option<u64> {
+        self.eq_id
+    }
+
+    pub fn sponsor_eq_id(&self) -> Option<u64> {
+        self.sponsor_eq_id
+    }
+
+    pub fn parent_eq_id(&self) -> Option<u64> {
+        self.parent_eq_id
+    }
+
+    pub fn created_date(&self) -> Option<DateTime<Utc>> {
+        self.created_date
+    }
+
+    pub fn change_date(&self) -> Option<DateTime<Utc>> {
+        self.change_date
+    }
+
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    pub fn invoice_phone_number(&self) -> &str {
+        &self.invoice_phone_number
+    }
+
+    pub fn delivery_phone_number(&self) -> &str {
+        &self.delivery_phone_number
+    }
+
+    pub fn invoice_address(&self) -> &str {
+        &self.invoice_address
+    }
+
+    pub fn shipping_address(&self) -> &str {
+        &self.shipping_address
+    }
+}
+
+/// formats of a date with an offset, other than rfc3339
+const OFFSET_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%:z",
+    "%Y-%m-%d %H:%M:%S%z",
+    "%Y-%m-%dT%H:%M:%S%z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+];
+
+/// formats of a date without an offset, which is in the timezone of the export
+const LOCAL_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+];
+
+/// Parse a date of the export into utc.
+/// A date with an offset is converted as is, a date without is in the `timezone` of the export,
+/// and a date without a time is at midnight of that day.
+pub(crate) fn format_date(date: &str, timezone: Tz) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(date) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Some(dt) = OFFSET_FORMATS
+        .iter()
+        .find_map(|format| DateTime::parse_from_str(date, format).ok())
+    {
+        return Some(dt.with_timezone(&Utc));
+    }
+    let local = LOCAL_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })?;
+    // the earlier of an ambiguous time when the clock is turned back,
+    // a time skipped when the clock is turned forward does not exist
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+
+/// wraps the reader in a gzip decoder if the content starts with the gzip magic bytes,
+/// otherwise the content is read as plain csv
+fn decode<'a>(reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>, Error> {
+    let mut reader = BufReader::new(reader);
+    let is_gzip = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if is_gzip {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// the customer data embedded in the binary
+#[cfg(feature = "embedded")]
+pub(crate) fn embedded_data() -> std::io::Cursor<&'static [u8]> {
+    std::io::Cursor::new(DATA)
+}
+
+/// How the values which can not be parsed are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// fail the load on the first value or record which can not be parsed
+    Strict,
+    /// the value is loaded as null, a record which can not be read is skipped,
+    /// and both are reported as warnings
+    #[default]
+    Lenient,
+}
+
+/// Options for loading the customer data
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub mode: ParseMode,
+    /// the timezone of the dates in the export which has no offset, defaults to utc
+    pub timezone: Tz,
+    /// replace the names, phone numbers and addresses with deterministic tokens
+    pub masking: Option<Masking>,
+}
+
+/// The loaded customer data and the warnings encountered in lenient mode
+pub struct CustomerData {
+    pub data_source: DataSource,
+    pub warnings: Vec<RowError>,
+    /// the parsed records in the order of the export, with the repeated and missing eq_id,
+    /// so they can be checked and searched without parsing the export again
+    pub customers: Vec<Customer>,
+    /// the options the data was loaded with, a newer export is applied with the same ones
+    pub options: LoadOptions,
+    /// the rows of the customer table written from the records, a newer export only writes again the changed ones
+    pub rows: TableRows,
+}
+
+/// The rows of the customer table, one per record in the same order, as they are written in the csv read by gauntlet.
+/// They are kept so the table of a newer export is built without deriving the address and phone columns
+/// of the records which did not change.
+#[derive(Debug, Clone, Default)]
+pub struct TableRows {
+    pub(crate) rows: Vec<StringRecord>,
+}
+
+/// The parsed customer records and the warnings encountered in lenient mode
+pub(crate) struct Parsed {
+    pub(crate) customers: Vec<Customer>,
+    pub(crate) warnings: Vec<RowError>,
+}
+
+/// Parses the values of a single record from the first column onwards,
+/// reporting the failures according to the `mode`
+pub(crate) struct RecordParser<'a> {
+    record: &'a StringRecord,
+    mode: ParseMode,
+    timezone: Tz,
+    warnings: &'a mut Vec<RowError>,
+    columns: &'static [Column],
+    /// the index of the next column to be parsed
+    index: usize,
+}
+
+/// fail in strict mode, otherwise keep the error as a warning
+fn report(mode: ParseMode, warnings: &mut Vec<RowError>, error: RowError) -> Result<(), Error> {
+    match mode {
+        ParseMode::Strict => Err(Error::RowError(error)),
+        ParseMode::Lenient => {
+            warnings.push(error);
+            Ok(())
+        }
+    }
+}
+
+impl<'a> RecordParser<'a> {
+    /// the raw value of the next column
+    fn next_value(&mut self) -> (usize, &'a str) {
+        let index = self.index;
+        self.index += 1;
+        (index, self.record.get(index).unwrap_or_default())
+    }
+
+    /// An empty value is null, anything else that fails to parse is an error in strict mode,
+    /// or a warning in lenient mode where the value becomes null.
+    pub(crate) fn parse<T>(&mut self, parse: impl Fn(&str) -> Option<T>, expected: &str) -> Result<Option<T>, Error> {
+        let (index, value) = self.next_value();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        match parse(value) {
+            Some(parsed) => Ok(Some(parsed)),
+            None => {
+                let error = RowError {
+                    row: self.record.position().map(|p| p.line()),
+                    column: Some(self.columns[index].name.to_string()),
+                    value: value.to_string(),
+                    reason: format!("expecting {expected}"),
+                };
+                report(self.mode, self.warnings, error)?;
+                Ok(None)
+            }
+        }
+    }
+
+    pub(crate) fn text(&mut self) -> String {
+        self.next_value().1.to_string()
+    }
+
+    pub(crate) fn timezone(&self) -> Tz {
+        self.timezone
+    }
+}
+
+/// Reads the customer records one at a time.
+/// In strict mode an invalid record is returned as an error and the reading can continue with the next record,
+/// in lenient mode the invalid values are kept as warnings and a record which can not be read is skipped.
+pub struct CustomerRecords<'a> {
+    records: csv::StringRecordsIntoIter<Box<dyn Read + 'a>>,
+    mode: ParseMode,
+    timezone: Tz,
+    warnings: Vec<RowError>,
+}
+
+impl<'a> CustomerRecords<'a> {
+    /// read the csv or gzipped csv content with the given options
+    pub fn new(reader: impl Read + 'a, options: &LoadOptions) -> Result<Self, Error> {
+        let mode = options.mode;
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(b',')
+            .has_headers(true)
+            .from_reader(decode(reader)?);
+
+        let mut warnings = vec![];
+        let headers = rdr.headers()?.clone();
+        for (i, column) in Customer::COLUMNS.iter().enumerate() {
+            let found = headers.get(i).unwrap_or_default();
+            if found != column.name {
+                let error = RowError {
+                    row: Some(1),
+                    column: Some(column.name.to_string()),
+                    value: found.to_string(),
+                    reason: "is not in the export header at this position".to_string(),
+                };
+                report(mode, &mut warnings, error)?;
+            }
+        }
+        Ok(CustomerRecords {
+            records: rdr.into_records(),
+            mode,
+            timezone: options.timezone,
+            warnings,
+        })
+    }
+
+    /// the warnings encountered so far in lenient mode
+    pub fn warnings(&self) -> &[RowError] {
+        &self.warnings
+    }
 }
 
+impl Iterator for CustomerRecords<'_> {
+    type Item = Result<Customer, Error>;
 
-fn format_date(date: &str) -> Option<NaiveDateTime> {
-    let dt = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S");
-    dt.ok()
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let result = match self.records.next()? {
+                Ok(result) => result,
+                Err(e) if self.mode == ParseMode::Lenient && !matches!(e.kind(), csv::ErrorKind::Io(_)) => {
+                    self.warnings.push(RowError {
+                        row: e.position().map(|p| p.line()),
+                        column: None,
+                        value: String::new(),
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => return Some(Err(e.into())),
+            };
+            let mut parser = RecordParser {
+                record: &result,
+                mode: self.mode,
+                timezone: self.timezone,
+                warnings: &mut self.warnings,
+                columns: Customer::COLUMNS,
+                index: 0,
+            };
+            return Some(Customer::from_record(&mut parser));
+        }
+    }
 }
 
+/// Stream the customer records from a csv or gzipped csv content.
+/// Each record which can not be parsed is an error with the row, column and raw value,
+/// the records after it can still be read.
+pub fn customers<'a>(reader: impl Read + 'a) -> impl Iterator<Item = Result<Customer, Error>> + 'a {
+    let options = LoadOptions {
+        mode: ParseMode::Strict,
+        ..Default::default()
+    };
+    let (error, records) = match CustomerRecords::new(reader, &options) {
+        Ok(records) => (None, Some(records)),
+        Err(e) => (Some(Err(e)), None),
+    };
+    error.into_iter().chain(records.into_iter().flatten())
+}
+
+/// parse the customer records from a csv or gzipped csv content
+pub(crate) fn parse_customers(reader: impl Read, options: &LoadOptions) -> Result<Parsed, Error> {
+    log::info!("Reading customers data..");
+    let mut records = CustomerRecords::new(reader, options)?;
+    let customers = records.by_ref().collect::<Result<Vec<_>, _>>()?;
+    let warnings = records.warnings;
+    if !warnings.is_empty() {
+        log::warn!("{} value(s) could not be parsed", warnings.len());
+    }
+    Ok(Parsed { customers, warnings })
+}
 
-/// This example demonstrates executing a simple query against a custom datasource
+/// load the customer data embedded in the binary
+#[cfg(feature = "embedded")]
 pub async fn customer_data() -> Result<DataSource, Error> {
     log::info!("in customer main..");
-    let in_file = Cursor::new(DATA);
-    let mut rdr = csv::ReaderBuilder::new()
-        .delimiter(b',')
-        .has_headers(true)
-        .from_reader(BufReader::new(GzDecoder::new(BufReader::new(in_file))));
+    customer_data_from_reader(embedded_data()).await
+}
 
-    log::info!("Reading customers data..");
-    let mut customers = vec![];
-    for result in rdr.records() {
-        let result = result?;
-        let eq_id = result[0].parse().ok();
-        let sponsor_eq_id = result[1].parse().ok();
-        let parent_eq_id = result[2].parse().ok();
-        let created_date = format_date(&result[3]);
-        let change_date = format_date(&result[4]);
-        let full_name = result[5].to_string();
-        let invoice_phone_number = result[6].to_string();
-        let delivery_phone_number = result[7].to_string();
-        let invoice_address = result[8].to_string();
-        let shipping_address = result[9].to_string();
-
-        let customer =  Customer{
-            eq_id,
-            sponsor_eq_id,
-            parent_eq_id,
-            created_date,
-            change_date,
-            full_name,
-            invoice_phone_number,
-            delivery_phone_number,
-            invoice_address,
-            shipping_address,
-        };
-        //log::info!("customer: {:#?}", customer);
-        customers.push(customer);
+/// load the customer data embedded in the binary with the given options
+#[cfg(feature = "embedded")]
+pub async fn customer_data_with(options: &LoadOptions) -> Result<CustomerData, Error> {
+    load(embedded_data(), options).await
+}
+
+/// load the customer data from a csv or gzipped csv file at `path`
+pub async fn customer_data_from_path(path: impl AsRef<Path>) -> Result<DataSource, Error> {
+    Ok(load_path(path, &LoadOptions::default()).await?.data_source)
+}
+
+/// load the customer data from a csv or gzipped csv content,
+/// the compression is detected from the magic bytes of the content
+pub async fn customer_data_from_reader(reader: impl Read) -> Result<DataSource, Error> {
+    Ok(load(reader, &LoadOptions::default()).await?.data_source)
+}
 
+/// load the customer data from a csv or gzipped csv file at `path` with the given options
+pub async fn load_path(path: impl AsRef<Path>, options: &LoadOptions) -> Result<CustomerData, Error> {
+    let path = path.as_ref();
+    log::info!("Loading customers from {}..", path.display());
+    load(File::open(path)?, options).await
+}
+
+/// the columns of the customer table, the record columns followed by the derived ones
+pub fn customer_table_columns() -> Vec<Column> {
+    [Customer::COLUMNS, AddressColumns::COLUMNS, PhoneColumns::COLUMNS].concat()
+}
+
+/// Build the customer table from the records, with the address and phone columns
+/// derived from them, masked when the options asks for it.
+pub fn customer_table(customers: &[Customer], options: &LoadOptions) -> Result<DataSource, Error> {
+    Ok(DataSource::from_csv(customer_table_csv(customers, options)?)?)
+}
+
+/// The customer table as the csv read by gauntlet, with the type header.
+/// A masked table can be shared and loaded with [`load_table`] where the key and the clear export can not go.
+pub fn customer_table_csv(customers: &[Customer], options: &LoadOptions) -> Result<Vec<u8>, Error> {
+    log::info!("Building the customer table..");
+    let mut table = TableWriter::new(&customer_table_columns());
+    for customer in customers {
+        table.row(|record| write_row(customer, options, record))?;
+    }
+    table.into_csv()
+}
+
+/// push the columns of the customer table of a record, the address and phone columns are derived
+/// from the clear values before they are masked
+fn write_row(customer: &Customer, options: &LoadOptions, record: &mut StringRecord) {
+    let address = AddressColumns::from(customer);
+    let phone = PhoneColumns::from(customer);
+    match &options.masking {
+        Some(masking) => {
+            customer.masked(masking).write_fields(record);
+            address.masked(masking).write_fields(record);
+            phone.masked(masking).write_fields(record);
+        }
+        None => {
+            customer.write_fields(record);
+            address.write_fields(record);
+            phone.write_fields(record);
+        }
+    }
+}
+
+impl TableRows {
+    /// write the row of each record, masked when the options asks for it
+    pub fn new(customers: &[Customer], options: &LoadOptions) -> Self {
+        log::info!("Building the customer table..");
+        let rows = customers.iter().map(|customer| TableRows::row(customer, options)).collect();
+        TableRows { rows }
     }
 
-    log::info!("Creating a csv..");
-    let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
-    for c in customers.iter(){
-        wtr.serialize(c)?;
+    pub(crate) fn row(customer: &Customer, options: &LoadOptions) -> StringRecord {
+        let mut record = StringRecord::new();
+        write_row(customer, options, &mut record);
+        record
     }
-    log::info!("done writing csv..");
 
-    let header = "{eq_id:u64?,sponsor_eq_id:u64?,parent_eq_id:u64?,created_date:utc?,change_date:utc?,full_name:text,invoice_phone_number:text,delivery_phone_number:text,invoice_address:text,shipping_address:text}";
-    let data = format!("{}\n{}",header,String::from_utf8(wtr.into_inner().unwrap())?);
-    let data_source = DataSource::from_csv(data.into_bytes())?;
-    Ok(data_source)
+    /// the customer table of the rows
+    pub fn data_source(&self) -> Result<DataSource, Error> {
+        let mut table = TableWriter::new(&customer_table_columns());
+        for row in self.rows.iter() {
+            table.write(row)?;
+        }
+        table.finish()
+    }
+}
+
+/// load a customer table written by [`customer_table_csv`], plain or gzipped
+pub fn load_table(reader: impl Read) -> Result<DataSource, Error> {
+    let mut csv = vec![];
+    decode(reader)?.read_to_end(&mut csv)?;
+    Ok(DataSource::from_csv(csv)?)
+}
+
+/// load the customer data from a csv or gzipped csv content with the given options
+pub async fn load(reader: impl Read, options: &LoadOptions) -> Result<CustomerData, Error> {
+    let Parsed { customers, warnings } = parse_customers(reader, options)?;
+    let rows = TableRows::new(&customers, options);
+    Ok(CustomerData {
+        data_source: rows.data_source()?,
+        warnings,
+        customers,
+        options: options.clone(),
+        rows,
+    })
 }
 
 
 #[cfg(test)]
 mod tests{
     use super::*;
-    use chrono::NaiveDate;
-    use chrono::NaiveTime;
 
+    fn strict() -> LoadOptions {
+        LoadOptions {
+            mode: ParseMode::Strict,
+            ..Default::default()
+        }
+    }
+
+    fn lenient() -> LoadOptions {
+        LoadOptions::default()
+    }
+
+    static SAMPLE: &[u8] = include_bytes!("../../../data/customer_sample.csv");
+
+    #[cfg(feature = "embedded")]
     #[tokio::test]
     async fn customer(){
         customer_data().await.unwrap();
     }
 
+    #[test]
+    fn parse_plain_and_gzip(){
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let plain = parse_customers(SAMPLE, &strict()).unwrap().customers;
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(SAMPLE).unwrap();
+        let gzipped = parse_customers(std::io::Cursor::new(encoder.finish().unwrap()), &strict()).unwrap().customers;
+        assert_eq!(plain.len(), 99);
+        assert_eq!(gzipped.len(), plain.len());
+        assert_eq!(plain[0].eq_id, Some(11810572));
+        assert_eq!(gzipped[0].full_name, "Test12 Test12");
+    }
+
+    #[test]
+    fn masked_table_without_the_clear_values(){
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let customers = parse_customers(SAMPLE, &strict()).unwrap().customers;
+        let options = LoadOptions {
+            masking: Some(Masking::new("demo")),
+            ..strict()
+        };
+        let csv = customer_table_csv(&customers, &options).unwrap();
+        let text = String::from_utf8_lossy(&csv);
+        assert!(!text.contains(customers[0].full_name.as_str()));
+        assert!(!text.contains(customers[0].invoice_phone_number.as_str()));
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&csv).unwrap();
+        load_table(encoder.finish().unwrap().as_slice()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn customer_from_path(){
+        customer_data_from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/../../data/customer_sample.csv")).await.unwrap();
+    }
+
+    const BAD_ROWS: &str = r#""eq_id","sponsor_eq_id","parent_eq_id","created_date","change_date","full_name","invoice_phone_number","delivery_phone_number","invoice_address","shipping_address"
+1,,,"2020-01-01 00:00:00","2020-01-01 00:00:00","Root",,,,
+2,1,abc,"2020-01-01 00:00:00","2020-01-01 00:00:00","Bad parent",,,,
+3,1,1,"01/01/2020","2020-01-01 00:00:00","Bad date",,,,
+4,1,1,"2020-01-01 00:00:00"
+"#;
+
+    #[test]
+    fn strict_mode_fails_with_context(){
+        let error = match parse_customers(BAD_ROWS.as_bytes(), &strict()) {
+            Err(Error::RowError(error)) => error,
+            _ => panic!("expecting a row error"),
+        };
+        assert_eq!(error.row, Some(3));
+        assert_eq!(error.column.as_deref(), Some("parent_eq_id"));
+        assert_eq!(error.value, "abc");
+    }
+
+    #[test]
+    fn lenient_mode_collects_warnings(){
+        let Parsed { customers, warnings } = parse_customers(BAD_ROWS.as_bytes(), &lenient()).unwrap();
+        assert_eq!(customers.len(), 3);
+        assert_eq!(customers[1].parent_eq_id, None);
+        assert_eq!(customers[2].created_date, None);
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[1].row, Some(4));
+        assert_eq!(warnings[1].column.as_deref(), Some("created_date"));
+        assert_eq!(warnings[2].row, Some(5));
+        assert_eq!(warnings[2].column, None);
+    }
+
+    #[test]
+    fn stream_continues_after_error(){
+        let results: Vec<_> = customers(BAD_ROWS.as_bytes()).collect();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().full_name(), "Root");
+        assert!(matches!(results[1], Err(Error::RowError(RowError { row: Some(3), .. }))));
+        assert!(matches!(results[2], Err(Error::RowError(RowError { row: Some(4), .. }))));
+        assert!(matches!(results[3], Err(Error::CsvError(_))));
+    }
+
     #[test]
     fn test_parse_date(){
-        let date = format_date("1970-01-01 00:00:00");
+        let date = format_date("1970-01-01 00:00:00", Tz::UTC);
         dbg!(&date);
-        let nd = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
-        let nt = NaiveTime::from_hms_milli_opt(0,0,0,0).unwrap();
-        assert_eq!(date, Some(NaiveDateTime::new(nd, nt)))
+        assert_eq!(date, Some(DateTime::UNIX_EPOCH))
+    }
+
+    #[test]
+    fn parse_date_in_timezone(){
+        let oslo = Tz::Europe__Oslo;
+        let utc = |s: &str| Some(s.parse::<DateTime<Utc>>().unwrap());
+        // +01:00 in the winter, +02:00 in the summer
+        assert_eq!(format_date("2024-01-15 10:00:00", oslo), utc("2024-01-15T09:00:00Z"));
+        assert_eq!(format_date("2024-07-15 10:00:00", oslo), utc("2024-07-15T08:00:00Z"));
+        assert_eq!(format_date("2024-07-15", oslo), utc("2024-07-14T22:00:00Z"));
+        // an explicit offset is not affected by the timezone of the export
+        assert_eq!(format_date("2024-07-15T10:00:00+05:00", oslo), utc("2024-07-15T05:00:00Z"));
+        assert_eq!(format_date("2024-07-15T10:00:00Z", oslo), utc("2024-07-15T10:00:00Z"));
+        assert_eq!(format_date("2024-07-15 10:00:00+0100", oslo), utc("2024-07-15T09:00:00Z"));
+        // skipped when the clock is turned forward
+        assert_eq!(format_date("2024-03-31 02:30:00", oslo), None);
+        assert_eq!(format_date("15/07/2024", oslo), None);
     }
 }
@@ -0,0 +1,447 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt;
+use gauntlet::DataSource;
+use crate::schema::{table, Column, Record, Table};
+use crate::Customer;
+use crate::CustomerData;
+use crate::Error;
+use crate::Masking;
+use crate::PhoneStatus;
+
+/// the weight of each signal in the score of a candidate pair
+const NAME_WEIGHT: f64 = 0.4;
+const PHONE_WEIGHT: f64 = 0.35;
+const ADDRESS_WEIGHT: f64 = 0.25;
+/// names which shares less than this are not counted at all
+const MIN_NAME_SIMILARITY: f64 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct DedupeOptions {
+    /// the minimum score, from 0.0 to 1.0, for a pair to be considered a duplicate
+    pub threshold: f64,
+    /// Customers sharing a phone number, address or name are compared with each other.
+    /// A group bigger than this is skipped, which happens with placeholder values shared by a lot of customers.
+    pub max_group_size: usize,
+}
+
+impl Default for DedupeOptions {
+    fn default() -> Self {
+        DedupeOptions {
+            threshold: 0.6,
+            max_group_size: 50,
+        }
+    }
+}
+
+/// Why a pair of customers is considered the same person
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchReason {
+    /// the share of the name tokens in common, from 0.0 to 1.0
+    Name { similarity: f64 },
+    /// the E.164 phone number in common
+    Phone(String),
+    /// the normalized street, postal code and country in common
+    Address(String),
+}
+
+/// A pair of customers which are likely the same person
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicatePair {
+    pub eq_id: u64,
+    pub duplicate_eq_id: u64,
+    pub score: f64,
+    pub reasons: Vec<MatchReason>,
+}
+
+/// Customers connected through duplicate pairs
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateCluster {
+    pub eq_ids: Vec<u64>,
+    pub pairs: Vec<DuplicatePair>,
+}
+
+/// the values of a customer which are compared
+struct Signature {
+    eq_id: u64,
+    name: BTreeSet<String>,
+    phones: BTreeSet<String>,
+    addresses: BTreeSet<String>,
+}
+
+impl Signature {
+    fn new(customer: &Customer) -> Option<Self> {
+        let eq_id = customer.eq_id?;
+        let phones = [customer.invoice_phone(), customer.delivery_phone()]
+            .into_iter()
+            .filter(|phone| phone.status == PhoneStatus::Valid)
+            .filter_map(|phone| phone.e164)
+            .collect();
+        let addresses = [customer.invoice_address_parts(), customer.shipping_address_parts()]
+            .into_iter()
+            .filter_map(|address| {
+                let street = normalize_text(address.street.as_deref()?);
+                let postal_code = address.postal_code?.replace(' ', "").to_lowercase();
+                let country = address.country?;
+                Some(format!("{street}, {postal_code}, {country}"))
+            })
+            .collect();
+        Some(Signature {
+            eq_id,
+            name: name_tokens(&customer.full_name),
+            phones,
+            addresses,
+        })
+    }
+
+    fn compare(&self, other: &Signature) -> DuplicatePair {
+        let mut reasons = vec![];
+        let mut score = 0.0;
+        let similarity = name_similarity(&self.name, &other.name);
+        if similarity >= MIN_NAME_SIMILARITY {
+            score += NAME_WEIGHT * similarity;
+            reasons.push(MatchReason::Name { similarity });
+        }
+        if let Some(phone) = self.phones.intersection(&other.phones).next() {
+            score += PHONE_WEIGHT;
+            reasons.push(MatchReason::Phone(phone.clone()));
+        }
+        if let Some(address) = self.addresses.intersection(&other.addresses).next() {
+            score += ADDRESS_WEIGHT;
+            reasons.push(MatchReason::Address(address.clone()));
+        }
+        DuplicatePair {
+            eq_id: self.eq_id.min(other.eq_id),
+            duplicate_eq_id: self.eq_id.max(other.eq_id),
+            score,
+            reasons,
+        }
+    }
+}
+
+/// replace the accented latin letters with their base letter
+fn fold(c: char) -> &'static str {
+    match c {
+        'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' | 'ą' | 'ă' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'é' | 'è' | 'ê' | 'ë' | 'ę' | 'ě' => "e",
+        'í' | 'ì' | 'î' | 'ï' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' => "n",
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' | 'ő' => "o",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' | 'ș' => "s",
+        'ß' => "ss",
+        'ț' | 'ţ' => "t",
+        'ú' | 'ù' | 'û' | 'ü' | 'ů' | 'ű' => "u",
+        'ý' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        'ğ' => "g",
+        _ => "",
+    }
+}
+
+/// lower case, without accents, and only the letters and digits separated by a single space
+fn normalize_text(text: &str) -> String {
+    let mut normalized = String::new();
+    for c in text.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            normalized.push(c);
+        } else if !fold(c).is_empty() {
+            normalized.push_str(fold(c));
+        } else if c.is_alphanumeric() {
+            normalized.push(c);
+        } else {
+            normalized.push(' ');
+        }
+    }
+    normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The words of a name, leaving out the notes in parenthesis, the digits
+/// and the single letters, eg: `Jørn (20231018_v2) Eriksen` is `{eriksen, jorn}`
+fn name_tokens(full_name: &str) -> BTreeSet<String> {
+    let mut without_notes = String::new();
+    let mut depth = 0;
+    for c in full_name.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = (depth - 1).max(0),
+            _ if depth == 0 => without_notes.push(c),
+            _ => (),
+        }
+    }
+    let letters: String = normalize_text(&without_notes)
+        .chars()
+        .map(|c| if c.is_ascii_digit() { ' ' } else { c })
+        .collect();
+    letters
+        .split_whitespace()
+        .filter(|token| token.chars().count() > 1)
+        .map(str::to_string)
+        .collect()
+}
+
+/// The share of the tokens of the shorter name found in the other name,
+/// so a name with extra words still matches, eg: `Test Jorn Eriksen` and `Jorn Eriksen`
+fn name_similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let shortest = a.len().min(b.len());
+    if shortest == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / shortest as f64
+}
+
+/// Find the customers which are likely the same person.
+/// Only the customers sharing a name, phone number or address are compared,
+/// and the pairs scoring at least the threshold are grouped into clusters.
+pub fn find_duplicates(customers: &[Customer], options: &DedupeOptions) -> Vec<DuplicateCluster> {
+    let signatures: Vec<Signature> = customers.iter().filter_map(Signature::new).collect();
+
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, signature) in signatures.iter().enumerate() {
+        let name = signature.name.iter().cloned().collect::<Vec<_>>().join(" ");
+        let keys = signature
+            .phones
+            .iter()
+            .map(|phone| format!("phone:{phone}"))
+            .chain(signature.addresses.iter().map(|address| format!("address:{address}")))
+            .chain((!name.is_empty()).then(|| format!("name:{name}")));
+        for key in keys {
+            groups.entry(key).or_default().push(i);
+        }
+    }
+
+    let mut candidates = BTreeSet::new();
+    for (key, members) in groups.iter() {
+        if members.len() > options.max_group_size {
+            log::warn!("skipping {key} which is shared by {} customers", members.len());
+            continue;
+        }
+        for (n, a) in members.iter().enumerate() {
+            for b in members[n + 1..].iter() {
+                if signatures[*a].eq_id != signatures[*b].eq_id {
+                    candidates.insert((*a.min(b), *a.max(b)));
+                }
+            }
+        }
+    }
+
+    let pairs: Vec<DuplicatePair> = candidates
+        .into_iter()
+        .map(|(a, b)| signatures[a].compare(&signatures[b]))
+        .filter(|pair| pair.score >= options.threshold)
+        .collect();
+    clusters(pairs)
+}
+
+/// find the duplicates in the customer data embedded in the binary
+#[cfg(feature = "embedded")]
+pub fn customer_duplicates(options: &DedupeOptions) -> Result<Vec<DuplicateCluster>, Error> {
+    let customers = crate::customer::parse_customers(crate::customer::embedded_data(), &Default::default())?.customers;
+    Ok(find_duplicates(&customers, options))
+}
+
+impl CustomerData {
+    /// Find the duplicates in the loaded records, without parsing the export again.
+    /// The phone numbers and addresses of the reasons are masked when the data was loaded with masking.
+    pub fn duplicates(&self, options: &DedupeOptions) -> Vec<DuplicateCluster> {
+        let clusters = find_duplicates(&self.customers, options);
+        match &self.options.masking {
+            Some(masking) => clusters.into_iter().map(|cluster| cluster.masked(masking)).collect(),
+            None => clusters,
+        }
+    }
+}
+
+impl MatchReason {
+    /// the phone number is the token of the masked E.164 number, the address is a token of its own
+    fn masked(self, masking: &Masking) -> Self {
+        match self {
+            MatchReason::Name { similarity } => MatchReason::Name { similarity },
+            MatchReason::Phone(e164) => MatchReason::Phone(masking.token("phone", &e164)),
+            MatchReason::Address(address) => MatchReason::Address(masking.token("address", &address)),
+        }
+    }
+}
+
+impl DuplicateCluster {
+    /// the cluster with the phone numbers and addresses of the reasons masked
+    pub fn masked(mut self, masking: &Masking) -> Self {
+        for pair in self.pairs.iter_mut() {
+            pair.reasons = std::mem::take(&mut pair.reasons)
+                .into_iter()
+                .map(|reason| reason.masked(masking))
+                .collect();
+        }
+        self
+    }
+}
+
+/// group the pairs which has a customer in common
+fn clusters(pairs: Vec<DuplicatePair>) -> Vec<DuplicateCluster> {
+    fn root(parent: &mut HashMap<u64, u64>, eq_id: u64) -> u64 {
+        let above = *parent.entry(eq_id).or_insert(eq_id);
+        if above == eq_id {
+            return eq_id;
+        }
+        let top = root(parent, above);
+        parent.insert(eq_id, top);
+        top
+    }
+    let mut parent = HashMap::new();
+    for pair in pairs.iter() {
+        let a = root(&mut parent, pair.eq_id);
+        let b = root(&mut parent, pair.duplicate_eq_id);
+        parent.insert(a.max(b), a.min(b));
+    }
+    let mut clusters: BTreeMap<u64, DuplicateCluster> = BTreeMap::new();
+    for pair in pairs {
+        let top = root(&mut parent, pair.eq_id);
+        let cluster = clusters.entry(top).or_insert_with(|| DuplicateCluster {
+            eq_ids: vec![],
+            pairs: vec![],
+        });
+        for eq_id in [pair.eq_id, pair.duplicate_eq_id] {
+            if !cluster.eq_ids.contains(&eq_id) {
+                cluster.eq_ids.push(eq_id);
+            }
+        }
+        cluster.pairs.push(pair);
+    }
+    clusters
+        .into_values()
+        .map(|mut cluster| {
+            cluster.eq_ids.sort();
+            cluster
+        })
+        .collect()
+}
+
+table! {
+    /// A duplicate pair in the `duplicate` table
+    pub(crate) struct DuplicateRow {
+        cluster: Option<u64>,
+        eq_id: Option<u64>,
+        duplicate_eq_id: Option<u64>,
+        score: f64,
+        reasons: String,
+    }
+}
+
+/// the columns of the `duplicate` table
+pub fn duplicate_table_columns() -> &'static [Column] {
+    DuplicateRow::COLUMNS
+}
+
+/// The duplicate pairs as a table, to be registered next to the customer table.
+/// The cluster is the lowest eq_id in the cluster.
+pub fn duplicate_data(clusters: &[DuplicateCluster]) -> Result<DataSource, Error> {
+    let rows: Vec<DuplicateRow> = clusters
+        .iter()
+        .flat_map(|cluster| {
+            cluster.pairs.iter().map(|pair| DuplicateRow {
+                cluster: cluster.eq_ids.first().copied(),
+                eq_id: Some(pair.eq_id),
+                duplicate_eq_id: Some(pair.duplicate_eq_id),
+                score: pair.score,
+                reasons: pair.reasons.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "),
+            })
+        })
+        .collect();
+    DuplicateRow::data_source(&rows)
+}
+
+impl fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchReason::Name { similarity } => write!(f, "name {:.0}%", similarity * 100.0),
+            MatchReason::Phone(phone) => write!(f, "phone {phone}"),
+            MatchReason::Address(address) => write!(f, "address {address}"),
+        }
+    }
+}
+
+impl fmt::Display for DuplicateCluster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:?}", self.eq_ids)?;
+        for pair in self.pairs.iter() {
+            let reasons: Vec<String> = pair.reasons.iter().map(ToString::to_string).collect();
+            writeln!(
+                f,
+                "  {} ~ {} ({:.2}): {}",
+                pair.eq_id,
+                pair.duplicate_eq_id,
+                pair.score,
+                reasons.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customers;
+    use crate::fixture::export;
+
+    fn duplicates(rows: &[&str]) -> Vec<DuplicateCluster> {
+        let customers: Vec<Customer> = customers(export(rows).as_bytes()).collect::<Result<_, _>>().unwrap();
+        find_duplicates(&customers, &DedupeOptions::default())
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            name_tokens("Jørn (20231018_v2) Eriksen (20220930)"),
+            BTreeSet::from(["eriksen".to_string(), "jorn".to_string()])
+        );
+        assert_eq!(
+            name_tokens("Test2 Jorn 20211206 v2 Eriksen"),
+            BTreeSet::from(["eriksen".to_string(), "jorn".to_string(), "test".to_string()])
+        );
+    }
+
+    #[test]
+    fn clusters_with_reasons() {
+        let clusters = duplicates(&[
+            r#"1618713,20543,20543,,,"Jørn (20231018_v2) Eriksen (20220930)",+4741454888,+4741454888,"Raadhuisstraat (2401231509), Amsterdam, 3036, NO","Raadhuisstraat (2401231509), Amsterdam, 3036, NO""#,
+            r#"1840258,20543,20543,,,"Test2 Jorn 20211206 v2 Eriksen",+4741454888,+4741454888,"Kb 108, Drammen, 3036, NO","Kb 108, Drammen, 3036, NO""#,
+            r#"1840259,20543,20543,,,"Jorn Eriksen",,,"Kb 108, Drammen, 3036, NO","Kb 108, Drammen, 3036, NO""#,
+            r#"4481722,20543,20543,,,"Anna Nilsen",+4553386041,+4553386041,"Gammel Viborgvej 51, Tilst, 8381, DK","Gammel Viborgvej 51, Tilst, 8381, DK""#,
+            r#"63632,32251,32357,,,"Tony Hansen",+4553386040,+4553386040,"Gammel Viborgvej 51, Tilst, 8381, DK","Gammel Viborgvej 51, Tilst, 8381, DK""#,
+        ]);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].eq_ids, vec![1618713, 1840258, 1840259]);
+        let pair = &clusters[0].pairs[0];
+        assert_eq!((pair.eq_id, pair.duplicate_eq_id), (1618713, 1840258));
+        assert_eq!(
+            pair.reasons,
+            vec![
+                MatchReason::Name { similarity: 1.0 },
+                MatchReason::Phone("+4741454888".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn masked_reasons() {
+        let masking = Masking::new("demo");
+        let clusters = duplicates(&[
+            r#"1840258,20543,20543,,,"Jorn Eriksen",+4741454888,,"Kb 108, Drammen, 3036, NO","#,
+            r#"1840259,20543,20543,,,"Jorn Eriksen",+4741454888,,"Kb 108, Drammen, 3036, NO","#,
+        ]);
+        let cluster = clusters[0].clone().masked(&masking);
+        assert_eq!(
+            cluster.pairs[0].reasons,
+            vec![
+                MatchReason::Name { similarity: 1.0 },
+                MatchReason::Phone(masking.token("phone", "+4741454888")),
+                MatchReason::Address(masking.token("address", "kb 108, 3036, NO")),
+            ]
+        );
+    }
+}
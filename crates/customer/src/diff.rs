@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use gauntlet::DataSource;
+use serde::Serialize;
+use crate::customer;
+use crate::schema::{table, Record};
+use crate::Customer;
+use crate::Error;
+use crate::LoadOptions;
+use crate::Move;
+
+/// A value of a customer which differs between the 2 exports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    pub eq_id: u64,
+    pub field: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// The differences between 2 snapshots of the customer export
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExportDiff {
+    /// the eq_id of the customers only in the newer export
+    pub added: Vec<u64>,
+    /// the eq_id of the customers only in the older export
+    pub removed: Vec<u64>,
+    pub changes: Vec<FieldChange>,
+    /// the customers placed under a different parent or sponsor
+    pub moves: Vec<Move>,
+}
+
+/// The customers with an eq_id, in the order of the export. Like the tree, the first record
+/// of a repeated eq_id is the customer, the later ones are left out.
+fn keyed(customers: &[Customer]) -> Vec<(u64, &Customer)> {
+    let mut seen = HashSet::new();
+    customers
+        .iter()
+        .filter_map(|customer| Some((customer.eq_id?, customer)))
+        .filter(|(eq_id, _)| seen.insert(*eq_id))
+        .collect()
+}
+
+impl ExportDiff {
+    /// Compare the customers of an older export to a newer one, keyed on `eq_id`.
+    /// The records without an eq_id can not be matched and are left out,
+    /// as are the repeated records of an eq_id after the first one.
+    pub fn new(before: &[Customer], after: &[Customer]) -> Self {
+        let before = keyed(before);
+        let after = keyed(after);
+        let before_index: HashMap<u64, &Customer> = before.iter().copied().collect();
+        let after_index: HashMap<u64, &Customer> = after.iter().copied().collect();
+
+        let mut diff = ExportDiff::default();
+        for (eq_id, _) in before.iter() {
+            if !after_index.contains_key(eq_id) {
+                diff.removed.push(*eq_id);
+            }
+        }
+        for (eq_id, customer) in after.iter() {
+            let Some(previous) = before_index.get(eq_id) else {
+                diff.added.push(*eq_id);
+                continue;
+            };
+            for (field, before, after) in previous.changes(customer) {
+                diff.changes.push(FieldChange {
+                    eq_id: *eq_id,
+                    field,
+                    before,
+                    after,
+                });
+            }
+            diff.moves.extend(previous.moves(*eq_id, customer));
+        }
+        diff
+    }
+
+    /// compare 2 csv or gzipped csv contents, both read with the given options
+    pub fn from_readers(before: impl Read, after: impl Read, options: &LoadOptions) -> Result<Self, Error> {
+        let before = customer::parse_customers(before, options)?.customers;
+        let after = customer::parse_customers(after, options)?.customers;
+        Ok(Self::new(&before, &after))
+    }
+
+    /// compare 2 csv or gzipped csv files, both read with the given options
+    pub fn from_paths(
+        before: impl AsRef<Path>,
+        after: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Self, Error> {
+        Self::from_readers(File::open(before)?, File::open(after)?, options)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changes.is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// the differences as rows, 1 per added, removed, changed value and move
+    pub fn rows(&self) -> Vec<DiffRow> {
+        let row = |change: &str, eq_id: u64, field: Option<&str>, before: Option<String>, after: Option<String>| DiffRow {
+            change: change.to_string(),
+            eq_id: Some(eq_id),
+            field: field.map(str::to_string),
+            before,
+            after,
+        };
+        let id = |eq_id: Option<u64>| eq_id.map(|eq_id| eq_id.to_string());
+        self.added
+            .iter()
+            .map(|eq_id| row("added", *eq_id, None, None, None))
+            .chain(self.removed.iter().map(|eq_id| row("removed", *eq_id, None, None, None)))
+            .chain(self.changes.iter().map(|change| {
+                row("changed", change.eq_id, Some(change.field), change.before.clone(), change.after.clone())
+            }))
+            .chain(self.moves.iter().map(|moved| {
+                let relation = moved.relation.to_string();
+                row("moved", moved.eq_id, Some(&relation), id(moved.from), id(moved.to))
+            }))
+            .collect()
+    }
+
+    /// the differences as a table which can be queried
+    pub fn data_source(&self) -> Result<DataSource, Error> {
+        DiffRow::data_source(&self.rows())
+    }
+}
+
+table! {
+    /// A difference between 2 exports, a row of the `diff` table
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct DiffRow {
+        /// added, removed, changed or moved
+        pub change: String,
+        pub eq_id: Option<u64>,
+        /// the column that changed, or the relation of a move
+        pub field: Option<String>,
+        pub before: Option<String>,
+        pub after: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::export;
+    use crate::Relation;
+
+    #[test]
+    fn month_over_month() {
+        let before = export(&[
+            r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#,
+            r#"2,1,1,"2020-01-01","2020-01-01","Child A",,,,"#,
+            r#"3,1,2,"2020-01-01","2020-01-01","Child B",,,,"#,
+        ]);
+        let after = export(&[
+            r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#,
+            r#"3,2,1,"2020-01-01","2020-02-01","Child B",,,,"#,
+            r#"4,3,3,"2020-02-01","2020-02-01","New",,,,"#,
+        ]);
+        let diff = ExportDiff::from_readers(before.as_bytes(), after.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(diff.added, vec![4]);
+        assert_eq!(diff.removed, vec![2]);
+        let fields: Vec<&str> = diff.changes.iter().map(|change| change.field).collect();
+        assert_eq!(fields, vec!["sponsor_eq_id", "parent_eq_id", "change_date"]);
+        assert_eq!(diff.changes[1].before.as_deref(), Some("2"));
+        assert_eq!(diff.changes[1].after.as_deref(), Some("1"));
+        assert_eq!(
+            diff.moves,
+            vec![
+                Move { eq_id: 3, relation: Relation::Parent, from: Some(2), to: Some(1) },
+                Move { eq_id: 3, relation: Relation::Sponsor, from: Some(1), to: Some(2) },
+            ]
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&diff.to_json().unwrap()).unwrap();
+        assert_eq!(json["moves"][0]["relation"], "parent");
+        assert_eq!(json["changes"][0]["field"], "sponsor_eq_id");
+
+        let rows = diff.rows();
+        let changes: Vec<&str> = rows.iter().map(|row| row.change.as_str()).collect();
+        assert_eq!(changes, vec!["added", "removed", "changed", "changed", "changed", "moved", "moved"]);
+        assert_eq!(
+            rows[5],
+            DiffRow {
+                change: "moved".to_string(),
+                eq_id: Some(3),
+                field: Some("parent".to_string()),
+                before: Some("2".to_string()),
+                after: Some("1".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn first_record_of_a_repeated_eq_id() {
+        let before = export(&[
+            r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#,
+            r#"1,,,"2020-01-01","2020-01-01","Root again",,,,"#,
+        ]);
+        let after = export(&[r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#]);
+        let diff = ExportDiff::from_readers(before.as_bytes(), after.as_bytes(), &LoadOptions::default()).unwrap();
+        assert!(diff.is_empty());
+        let diff = ExportDiff::from_readers(after.as_bytes(), before.as_bytes(), &LoadOptions::default()).unwrap();
+        assert!(diff.is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 use std::string::FromUtf8Error;
+use std::io;
+use std::fmt;
 
 #[derive(Debug, Error)]
 pub enum Error{
@@ -9,4 +11,36 @@ pub enum Error{
     CsvError(#[from] csv::Error),
     #[error("{0}")]
     DataError(#[from]gauntlet::Error),
+    #[error("{0}")]
+    IoError(#[from] io::Error),
+    #[error("{0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("{0}")]
+    QueryError(String),
+    #[error("{0}")]
+    RowError(RowError),
+}
+
+/// A record or value in the export which could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// the line in the export, the header is at line 1
+    pub row: Option<u64>,
+    /// the column of the value, none if the whole record could not be read
+    pub column: Option<String>,
+    /// the raw value
+    pub value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(row) = self.row {
+            write!(f, "row {row}: ")?;
+        }
+        match &self.column {
+            Some(column) => write!(f, "column `{column}` {}, found `{}`", self.reason, self.value),
+            None => write!(f, "{}", self.reason),
+        }
+    }
 }
@@ -0,0 +1,9 @@
+//! The csv exports written by the tests
+
+/// the header of an export
+pub(crate) const HEADER: &str = r#""eq_id","sponsor_eq_id","parent_eq_id","created_date","change_date","full_name","invoice_phone_number","delivery_phone_number","invoice_address","shipping_address""#;
+
+/// an export with the header and the `rows`
+pub(crate) fn export(rows: &[&str]) -> String {
+    format!("{HEADER}\n{}\n", rows.join("\n"))
+}
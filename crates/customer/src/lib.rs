@@ -1,5 +1,46 @@
-pub use error::Error;
-pub use customer::customer_data;
+pub use ::address::{Address, Confidence};
+pub use dedupe::{
+    duplicate_data, duplicate_table_columns, find_duplicates, DedupeOptions, DuplicateCluster, DuplicatePair,
+    MatchReason,
+};
+pub use diff::{DiffRow, ExportDiff, FieldChange};
+pub use error::{Error, RowError};
+pub use customer::{
+    customer_data_from_path, customer_data_from_reader, customer_table, customer_table_columns, customer_table_csv,
+    customers, load, load_path, load_table, Customer, CustomerData, CustomerRecords, LoadOptions, ParseMode,
+    TableRows,
+};
+pub use mask::{Masking, MASKING_KEY_VAR};
+pub use phone::{Phone, PhoneStatus};
+pub use query::{column, CustomerCount, FromRow, FromValue, Param, Query};
+pub use reload::{ChangeReport, Move, Update};
+pub use schema::{Column, Table};
+pub use tree::{CustomerTree, TreeNode};
+pub use validate::{Issue, ValidationReport};
+pub use walk::{DownlineEntry, DownlineOptions, Order, Relation};
 
+// the customer data embedded in the binary, the viewer embeds a masked table instead
+#[cfg(feature = "embedded")]
+pub use customer::{customer_data, customer_data_with};
+#[cfg(feature = "embedded")]
+pub use dedupe::customer_duplicates;
+#[cfg(feature = "embedded")]
+pub use tree::{customer_tree, customer_tree_with};
+#[cfg(feature = "embedded")]
+pub use validate::validate_customer_data;
+
+mod address;
 mod customer;
+mod dedupe;
+mod diff;
 mod error;
+#[cfg(test)]
+mod fixture;
+mod mask;
+mod phone;
+mod query;
+mod reload;
+mod schema;
+mod tree;
+mod validate;
+mod walk;
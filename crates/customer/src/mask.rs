@@ -0,0 +1,156 @@
+use std::fmt;
+use sha2::{Digest, Sha256};
+use crate::Address;
+use crate::Customer;
+use crate::Phone;
+
+/// the number of hex digits kept from the hash, enough to not collide in an export
+const TOKEN_LEN: usize = 12;
+/// the environment variable with the masking key
+pub const MASKING_KEY_VAR: &str = "CUSTOMER_MASKING_KEY";
+
+/// Replace the personal data of the customers with deterministic tokens,
+/// so the same value is always the same token and the tables can still be joined on it.
+///
+/// The key is mixed into the hash, phone numbers and postal codes are few enough
+/// to be guessed back from a plain hash. Keep the key out of what is shared,
+/// and out of the source: it is read from the environment.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Masking {
+    pub key: String,
+}
+
+/// the key is not printed
+impl fmt::Debug for Masking {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Masking").field("key", &"..").finish()
+    }
+}
+
+impl Masking {
+    pub fn new(key: impl Into<String>) -> Self {
+        Masking { key: key.into() }
+    }
+
+    /// the masking with the key in `CUSTOMER_MASKING_KEY`, None when it is not set or empty
+    pub fn from_env() -> Option<Self> {
+        std::env::var(MASKING_KEY_VAR)
+            .ok()
+            .filter(|key| !key.is_empty())
+            .map(Masking::new)
+    }
+
+    /// The token of a value, prefixed with its kind, eg: `name-3f9a0c1b2d4e`.
+    /// An empty value stays empty, so a missing value is still missing.
+    pub fn token(&self, kind: &str, value: &str) -> String {
+        let value = value.trim();
+        if value.is_empty() {
+            return String::new();
+        }
+        let mut hasher = Sha256::new();
+        for part in [self.key.as_str(), kind, value] {
+            hasher.update(part.as_bytes());
+            // the separator keeps `ab` + `c` apart from `a` + `bc`
+            hasher.update([0]);
+        }
+        let hex: String = hasher.finalize().iter().map(|b| format!("{b:02x}")).collect();
+        format!("{kind}-{}", &hex[..TOKEN_LEN])
+    }
+
+    /// Mask the street and postal code, the city and country are kept for the maps
+    /// and the address is written back so it can still be split into its components.
+    pub fn address(&self, raw: &str) -> String {
+        let address = Address::parse(raw);
+        let street = address.street.map(|street| self.token("street", &street));
+        if address.city.is_none() && address.postal_code.is_none() && address.country.is_none() {
+            return street.unwrap_or_default();
+        }
+        // every component is written at its place, so the components are taken from the end the same way
+        let postal_code = address.postal_code.map(|postal_code| self.token("postal", &postal_code));
+        [street, address.city, postal_code, address.country]
+            .map(Option::unwrap_or_default)
+            .join(", ")
+    }
+
+    /// Mask a phone number on its E.164 form when it has one,
+    /// so the same number written differently is the same token.
+    pub fn phone(&self, raw: &str, country: Option<&str>) -> String {
+        match Phone::parse(raw, country).e164 {
+            Some(e164) => self.token("phone", &e164),
+            None => self.token("phone", raw),
+        }
+    }
+}
+
+impl Customer {
+    /// The customer with its name, phone numbers and addresses masked.
+    /// The eq_ids and dates are kept, so the tree is the same.
+    pub fn masked(&self, masking: &Masking) -> Customer {
+        let invoice_country = self.invoice_address_parts().country;
+        let shipping_country = self.shipping_address_parts().country;
+        Customer {
+            full_name: masking.token("name", &self.full_name),
+            invoice_phone_number: masking.phone(&self.invoice_phone_number, invoice_country.as_deref()),
+            delivery_phone_number: masking.phone(&self.delivery_phone_number, shipping_country.as_deref()),
+            invoice_address: masking.address(&self.invoice_address),
+            shipping_address: masking.address(&self.shipping_address),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customer::parse_customers;
+    use crate::{CustomerTree, LoadOptions};
+
+    static SAMPLE: &[u8] = include_bytes!("../../../data/customer_sample.csv");
+
+    #[test]
+    fn deterministic_tokens() {
+        let masking = Masking::new("demo");
+        let token = masking.token("name", "Jørn Eriksen");
+        assert_eq!(token, masking.token("name", "Jørn Eriksen"));
+        assert!(token.starts_with("name-"));
+        assert_eq!(token.len(), "name-".len() + TOKEN_LEN);
+        assert_ne!(token, Masking::new("other").token("name", "Jørn Eriksen"));
+        assert_eq!(masking.token("name", " "), "");
+        // the same number written differently
+        assert_eq!(masking.phone("+47 414 54 888", None), masking.phone("41454888", Some("NO")));
+        assert!(!format!("{masking:?}").contains("demo"));
+    }
+
+    #[test]
+    fn masked_address_keeps_city_and_country() {
+        let masking = Masking::new("demo");
+        let masked = Address::parse(&masking.address("Kb 108, Drammen, 3036, NO"));
+        assert_eq!(masked.street, Some(masking.token("street", "Kb 108")));
+        assert_eq!(masked.city.as_deref(), Some("Drammen"));
+        assert_eq!(masked.postal_code, Some(masking.token("postal", "3036")));
+        assert_eq!(masked.country.as_deref(), Some("NO"));
+        let masked = Address::parse(&masking.address("Cracow, 55042, PL"));
+        assert_eq!(masked.street, None);
+        assert_eq!(masked.city.as_deref(), Some("Cracow"));
+        assert_eq!(masked.postal_code, Some(masking.token("postal", "55042")));
+    }
+
+    #[test]
+    fn masked_tree_is_the_same() {
+        let masking = Masking::new("demo");
+        let customers = parse_customers(SAMPLE, &LoadOptions::default()).unwrap().customers;
+        let masked: Vec<Customer> = customers.iter().map(|customer| customer.masked(&masking)).collect();
+        for (customer, masked) in customers.iter().zip(masked.iter()) {
+            assert_ne!(customer.full_name, masked.full_name);
+            assert!(!masked.invoice_address.contains(customer.invoice_address.as_str()));
+        }
+        let tree = CustomerTree::from_customers(customers);
+        let masked_tree = CustomerTree::from_customers(masked);
+        let roots: Vec<u64> = tree.roots().map(|node| node.eq_id()).collect();
+        let masked_roots: Vec<u64> = masked_tree.roots().map(|node| node.eq_id()).collect();
+        assert_eq!(roots, masked_roots);
+        for node in tree.iter() {
+            assert_eq!(tree.children(node.eq_id()), masked_tree.children(node.eq_id()));
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use crate::schema::table;
+use crate::Customer;
+use crate::Masking;
+
+/// The outcome of normalizing a phone number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhoneStatus {
+    Valid,
+    /// a well formed number, but which looks made up, eg: `+48123456789`
+    Placeholder,
+    /// the number has an unknown country calling code, or too few or too many digits
+    Invalid,
+    /// there is no number
+    Missing,
+}
+
+/// A phone number normalized to E.164, eg: `+48849823172`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phone {
+    /// the number in E.164, only when it is well formed
+    pub e164: Option<String>,
+    /// the country calling code, eg: `48` for Poland
+    pub calling_code: Option<u64>,
+    pub status: PhoneStatus,
+}
+
+/// (ISO 3166 alpha-2 country code, country calling code, trunk prefix)
+/// used to complete the numbers written without the international prefix. The trunk prefix is
+/// dialed before a national number within the country only, so it is dropped from the E.164 number;
+/// it is `None` where there is none, or where the leading `0` is part of the number, as in Italy.
+const COUNTRY_CALLING_CODES: &[(&str, u64, Option<&str>)] = &[
+    ("AD", 376, None), ("AE", 971, Some("0")), ("AF", 93, Some("0")), ("AL", 355, Some("0")),
+    ("AM", 374, Some("0")), ("AO", 244, None), ("AR", 54, Some("0")), ("AT", 43, Some("0")),
+    ("AU", 61, Some("0")), ("AZ", 994, Some("0")), ("BA", 387, Some("0")), ("BD", 880, Some("0")),
+    ("BE", 32, Some("0")), ("BG", 359, Some("0")), ("BH", 973, None), ("BO", 591, Some("0")),
+    ("BR", 55, Some("0")), ("BY", 375, Some("8")), ("CA", 1, Some("1")), ("CH", 41, Some("0")),
+    ("CL", 56, None), ("CN", 86, Some("0")), ("CO", 57, None), ("CR", 506, None),
+    ("CU", 53, Some("0")), ("CY", 357, None), ("CZ", 420, None), ("DE", 49, Some("0")),
+    ("DK", 45, None), ("DO", 1, Some("1")), ("DZ", 213, Some("0")), ("EC", 593, Some("0")),
+    ("EE", 372, None), ("EG", 20, Some("0")), ("ES", 34, None), ("ET", 251, Some("0")),
+    ("FI", 358, Some("0")), ("FO", 298, None), ("FR", 33, Some("0")), ("GB", 44, Some("0")),
+    ("GE", 995, Some("0")), ("GH", 233, Some("0")), ("GI", 350, None), ("GL", 299, None),
+    ("GR", 30, None), ("HK", 852, None), ("HR", 385, Some("0")), ("HU", 36, Some("06")),
+    ("ID", 62, Some("0")), ("IE", 353, Some("0")), ("IL", 972, Some("0")), ("IN", 91, Some("0")),
+    ("IQ", 964, Some("0")), ("IR", 98, Some("0")), ("IS", 354, None), ("IT", 39, None),
+    ("JO", 962, Some("0")), ("JP", 81, Some("0")), ("KE", 254, Some("0")), ("KR", 82, Some("0")),
+    ("KW", 965, None), ("KZ", 7, Some("8")), ("LB", 961, Some("0")), ("LI", 423, None),
+    ("LK", 94, Some("0")), ("LT", 370, Some("8")), ("LU", 352, None), ("LV", 371, None),
+    ("MA", 212, Some("0")), ("MC", 377, None), ("MD", 373, Some("0")), ("ME", 382, Some("0")),
+    ("MK", 389, Some("0")), ("MT", 356, None), ("MX", 52, None), ("MY", 60, Some("0")),
+    ("NG", 234, Some("0")), ("NL", 31, Some("0")), ("NO", 47, None), ("NZ", 64, Some("0")),
+    ("PE", 51, Some("0")), ("PH", 63, Some("0")), ("PK", 92, Some("0")), ("PL", 48, None),
+    ("PT", 351, None), ("QA", 974, None), ("RO", 40, Some("0")), ("RS", 381, Some("0")),
+    ("RU", 7, Some("8")), ("SA", 966, Some("0")), ("SE", 46, Some("0")), ("SG", 65, None),
+    ("SI", 386, Some("0")), ("SK", 421, Some("0")), ("SM", 378, None), ("TH", 66, Some("0")),
+    ("TN", 216, None), ("TR", 90, Some("0")), ("TW", 886, Some("0")), ("UA", 380, Some("0")),
+    ("US", 1, Some("1")), ("UY", 598, None), ("UZ", 998, Some("8")), ("VE", 58, Some("0")),
+    ("VN", 84, Some("0")), ("ZA", 27, Some("0")),
+];
+
+/// the assigned country calling codes, for the numbers written with the international prefix
+const CALLING_CODES: &[u64] = &[
+    1, 7, 20, 27, 30, 31, 32, 33, 34, 36, 39, 40, 41, 43, 44, 45, 46, 47, 48, 49, 51, 52, 53,
+    54, 55, 56, 57, 58, 60, 61, 62, 63, 64, 65, 66, 81, 82, 84, 86, 90, 91, 92, 93, 94, 95, 98,
+    211, 212, 213, 216, 218, 220, 221, 222, 223, 224, 225, 226, 227, 228, 229, 230, 231, 232,
+    233, 234, 235, 236, 237, 238, 239, 240, 241, 242, 243, 244, 245, 246, 248, 249, 250, 251,
+    252, 253, 254, 255, 256, 257, 258, 260, 261, 262, 263, 264, 265, 266, 267, 268, 269, 290,
+    291, 297, 298, 299, 350, 351, 352, 353, 354, 355, 356, 357, 358, 359, 370, 371, 372, 373,
+    374, 375, 376, 377, 378, 380, 381, 382, 383, 385, 386, 387, 389, 420, 421, 423, 500, 501,
+    502, 503, 504, 505, 506, 507, 508, 509, 590, 591, 592, 593, 594, 595, 596, 597, 598, 599,
+    670, 672, 673, 674, 675, 676, 677, 678, 679, 680, 681, 682, 683, 685, 686, 687, 688, 689,
+    690, 691, 692, 850, 852, 853, 855, 856, 880, 886, 960, 961, 962, 963, 964, 965, 966, 967,
+    968, 970, 971, 972, 973, 974, 975, 976, 977, 992, 993, 994, 995, 996, 998,
+];
+
+/// E.164 allows at most 15 digits including the calling code
+const MAX_DIGITS: usize = 15;
+/// the shortest national numbers in use have 4 digits
+const MIN_NATIONAL_DIGITS: usize = 4;
+
+impl Phone {
+    /// Normalize a phone number of the export.
+    /// A number without the international prefix `+` or `00` is completed with the calling code of `country`,
+    /// the ISO 3166 alpha-2 code of the address that goes with the number.
+    pub fn parse(raw: &str, country: Option<&str>) -> Self {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return Phone::status(PhoneStatus::Missing);
+        }
+        // separators which are commonly written in phone numbers
+        let cleaned: String = raw
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')' | '/'))
+            .collect();
+        let (international, digits) = if let Some(digits) = cleaned.strip_prefix('+') {
+            (true, digits)
+        } else if let Some(digits) = cleaned.strip_prefix("00") {
+            (true, digits)
+        } else {
+            (false, cleaned.as_str())
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Phone::status(PhoneStatus::Invalid);
+        }
+
+        let split = if international {
+            split_calling_code(digits)
+        } else {
+            country.and_then(country_of).map(|(calling_code, trunk_prefix)| {
+                let national = trunk_prefix.and_then(|prefix| digits.strip_prefix(prefix));
+                (calling_code, national.unwrap_or(digits))
+            })
+        };
+        let Some((calling_code, national)) = split else {
+            return Phone::status(PhoneStatus::Invalid);
+        };
+        let e164 = format!("+{calling_code}{national}");
+        if national.len() < MIN_NATIONAL_DIGITS || e164.len() - 1 > MAX_DIGITS {
+            return Phone {
+                e164: None,
+                calling_code: Some(calling_code),
+                status: PhoneStatus::Invalid,
+            };
+        }
+        let status = if is_placeholder(national) {
+            PhoneStatus::Placeholder
+        } else {
+            PhoneStatus::Valid
+        };
+        Phone {
+            e164: Some(e164),
+            calling_code: Some(calling_code),
+            status,
+        }
+    }
+
+    fn status(status: PhoneStatus) -> Self {
+        Phone {
+            e164: None,
+            calling_code: None,
+            status,
+        }
+    }
+}
+
+/// the calling code and the trunk prefix of a country
+fn country_of(country: &str) -> Option<(u64, Option<&'static str>)> {
+    COUNTRY_CALLING_CODES
+        .iter()
+        .find(|(code, ..)| code.eq_ignore_ascii_case(country))
+        .map(|(_, calling_code, trunk_prefix)| (*calling_code, *trunk_prefix))
+}
+
+/// calling codes are prefix free, so at most 1 of the first 1, 2 or 3 digits is assigned
+fn split_calling_code(digits: &str) -> Option<(u64, &str)> {
+    (1..=3).filter(|len| *len < digits.len()).find_map(|len| {
+        let calling_code: u64 = digits[..len].parse().ok()?;
+        CALLING_CODES
+            .contains(&calling_code)
+            .then(|| (calling_code, &digits[len..]))
+    })
+}
+
+/// A national number which is the same digit all along, like `000000000`, or counts
+/// up or down all along, like `123456789` or `987654321`, is a made up number.
+/// Only the whole number is checked, a real number can have a run like `12345` in it.
+fn is_placeholder(national: &str) -> bool {
+    let digits: Vec<i8> = national.bytes().map(|b| (b - b'0') as i8).collect();
+    let steps: Vec<i8> = digits.windows(2).map(|w| (w[1] - w[0]).rem_euclid(10)).collect();
+    // the steps of a counting number are all 1, or all 9 for a count down, and 9 to 0 is a step of 1
+    !steps.is_empty() && [0, 1, 9].iter().any(|step| steps.iter().all(|s| s == step))
+}
+
+impl fmt::Display for PhoneStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhoneStatus::Valid => write!(f, "valid"),
+            PhoneStatus::Placeholder => write!(f, "placeholder"),
+            PhoneStatus::Invalid => write!(f, "invalid"),
+            PhoneStatus::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+impl Customer {
+    /// the invoice phone number, completed with the country of the invoice address
+    pub fn invoice_phone(&self) -> Phone {
+        let country = self.invoice_address_parts().country;
+        Phone::parse(&self.invoice_phone_number, country.as_deref())
+    }
+
+    /// the delivery phone number, completed with the country of the shipping address
+    pub fn delivery_phone(&self) -> Phone {
+        let country = self.shipping_address_parts().country;
+        Phone::parse(&self.delivery_phone_number, country.as_deref())
+    }
+}
+
+table! {
+    /// The normalized phone numbers added to the customer table
+    pub(crate) struct PhoneColumns {
+        invoice_phone_e164: Option<String>,
+        invoice_phone_calling_code: Option<u64>,
+        invoice_phone_status: String,
+        delivery_phone_e164: Option<String>,
+        delivery_phone_calling_code: Option<u64>,
+        delivery_phone_status: String,
+    }
+}
+
+impl From<&Customer> for PhoneColumns {
+    fn from(customer: &Customer) -> Self {
+        let invoice = customer.invoice_phone();
+        let delivery = customer.delivery_phone();
+        PhoneColumns {
+            invoice_phone_e164: invoice.e164,
+            invoice_phone_calling_code: invoice.calling_code,
+            invoice_phone_status: invoice.status.to_string(),
+            delivery_phone_e164: delivery.e164,
+            delivery_phone_calling_code: delivery.calling_code,
+            delivery_phone_status: delivery.status.to_string(),
+        }
+    }
+}
+
+impl PhoneColumns {
+    /// mask the E.164 numbers the same way as in the masked phone numbers
+    pub(crate) fn masked(self, masking: &Masking) -> Self {
+        let phone = |e164: Option<String>| e164.map(|e164| masking.token("phone", &e164));
+        PhoneColumns {
+            invoice_phone_e164: phone(self.invoice_phone_e164),
+            delivery_phone_e164: phone(self.delivery_phone_e164),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid(e164: &str, calling_code: u64) -> Phone {
+        Phone {
+            e164: Some(e164.to_string()),
+            calling_code: Some(calling_code),
+            status: PhoneStatus::Valid,
+        }
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(Phone::parse("+48849823172", None), valid("+48849823172", 48));
+        assert_eq!(Phone::parse("0048 849-823-172", None), valid("+48849823172", 48));
+        assert_eq!(Phone::parse("+1 (415) 555 2671", None), valid("+14155552671", 1));
+        assert_eq!(Phone::parse("+4366473955520", None), valid("+4366473955520", 43));
+        // completed with the country of the address
+        assert_eq!(Phone::parse("0612345678", Some("NL")), valid("+31612345678", 31));
+        assert_eq!(Phone::parse("41454888", Some("no")), valid("+4741454888", 47));
+    }
+
+    #[test]
+    fn trunk_prefix_of_the_country() {
+        // the leading 0 is part of an italian number
+        assert_eq!(Phone::parse("06 1234 5678", Some("IT")), valid("+390612345678", 39));
+        assert_eq!(Phone::parse("06 30 123 4567", Some("HU")), valid("+36301234567", 36));
+        assert_eq!(Phone::parse("8 912 345 6789", Some("RU")), valid("+79123456789", 7));
+        assert_eq!(Phone::parse("0151 2345 6789", Some("DE")), valid("+4915123456789", 49));
+    }
+
+    #[test]
+    fn invalid_and_placeholder() {
+        assert_eq!(Phone::parse("", None).status, PhoneStatus::Missing);
+        assert_eq!(Phone::parse("n/a", None).status, PhoneStatus::Invalid);
+        assert_eq!(Phone::parse("41454888", None).status, PhoneStatus::Invalid);
+        assert_eq!(Phone::parse("+999123456", None).status, PhoneStatus::Invalid);
+        assert_eq!(Phone::parse("+48123", None).status, PhoneStatus::Invalid);
+        assert_eq!(Phone::parse("+4812345678901234", None).status, PhoneStatus::Invalid);
+        assert_eq!(Phone::parse("+48000000000", None).status, PhoneStatus::Placeholder);
+        assert_eq!(Phone::parse("+48123456789", None).status, PhoneStatus::Placeholder);
+        assert_eq!(Phone::parse("+48987654321", None).status, PhoneStatus::Placeholder);
+        assert_eq!(Phone::parse("+311234567890", None).status, PhoneStatus::Placeholder);
+        // a counting or repeated run in a real number
+        assert_eq!(Phone::parse("+48512345678", None).status, PhoneStatus::Valid);
+        assert_eq!(Phone::parse("0612345678", Some("NL")).status, PhoneStatus::Valid);
+        assert_eq!(Phone::parse("+38612345669", None).status, PhoneStatus::Valid);
+        assert_eq!(Phone::parse("+48500000007", None).status, PhoneStatus::Valid);
+    }
+}
@@ -0,0 +1,399 @@
+use gauntlet::{Context, DataPane, Value};
+use crate::Error;
+
+/// A value written in place of a `?` placeholder of a query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    Null,
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Text(String),
+}
+
+/// A query with `?` placeholders and the parameters which replace them in order,
+/// eg: `Query::new("SELECT * FROM customer WHERE eq_id = ?").arg(eq_id)`
+///
+/// gauntlet has no prepared statements, so the parameters are written into the sql as literals,
+/// a text quoted with its quotes doubled. A null parameter is written as `NULL`, which a comparison
+/// is never true for: a null check is written with [`Query::is_null`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    sql: String,
+    params: Vec<Param>,
+}
+
+/// A type which can be read from a value of the query result
+pub trait FromValue: Sized {
+    /// the name of the type in the error message
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+/// A type which can be read from a row of the query result
+pub trait FromRow: Sized {
+    fn from_row(row: &[Value]) -> Result<Self, Error>;
+}
+
+/// A customer and the number of its children, the result of the tree queries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomerCount {
+    pub eq_id: u64,
+    pub full_name: String,
+    pub children: u64,
+}
+
+impl Query {
+    pub fn new(sql: impl Into<String>) -> Self {
+        Query {
+            sql: sql.into(),
+            params: vec![],
+        }
+    }
+
+    /// the value of the next placeholder
+    pub fn arg(mut self, param: impl Into<Param>) -> Self {
+        self.params.push(param.into());
+        self
+    }
+
+    /// append the condition that `column` is null, eg: `Query::new("SELECT * FROM customer WHERE").is_null("parent_eq_id")`
+    pub fn is_null(mut self, column: &str) -> Self {
+        self.sql.push_str(&format!(" {column} IS NULL"));
+        self
+    }
+
+    /// The sql with the placeholders replaced by the parameters.
+    /// A `?` inside a quoted text or identifier, or a comment, is not a placeholder.
+    pub fn to_sql(&self) -> Result<String, Error> {
+        let mut sql = String::with_capacity(self.sql.len());
+        let mut params = self.params.iter();
+        let mut chars = self.sql.chars().peekable();
+        while let Some(c) = chars.next() {
+            sql.push(c);
+            match c {
+                // a doubled quote is read as the end and the start of a quote
+                '\'' | '"' | '`' => {
+                    for q in chars.by_ref() {
+                        sql.push(q);
+                        if q == c {
+                            break;
+                        }
+                    }
+                }
+                '-' if chars.peek() == Some(&'-') => {
+                    while let Some(q) = chars.next_if(|q| *q != '\n') {
+                        sql.push(q);
+                    }
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    let mut previous = ' ';
+                    for q in chars.by_ref() {
+                        sql.push(q);
+                        if previous == '*' && q == '/' {
+                            break;
+                        }
+                        previous = q;
+                    }
+                }
+                '?' => {
+                    sql.pop();
+                    let param = params.next().ok_or_else(|| {
+                        Error::QueryError(format!("missing a parameter for the placeholders of: {}", self.sql))
+                    })?;
+                    sql.push_str(&param.to_literal()?);
+                }
+                _ => (),
+            }
+        }
+        if params.next().is_some() {
+            return Err(Error::QueryError(format!(
+                "{} parameter(s) for fewer placeholders in: {}",
+                self.params.len(),
+                self.sql
+            )));
+        }
+        Ok(sql)
+    }
+
+    /// run the query and return the result as it is
+    pub async fn fetch_pane(&self, ctx: &Context) -> Result<DataPane, Error> {
+        Ok(ctx.sql(&self.to_sql()?).await?)
+    }
+
+    /// run the query and read each row into `T`
+    pub async fn fetch<T: FromRow>(&self, ctx: &Context) -> Result<Vec<T>, Error> {
+        let pane = self.fetch_pane(ctx).await?;
+        pane.row_values.iter().map(|row| T::from_row(row)).collect()
+    }
+
+    /// run the query and read the first value of the first row, eg: a `COUNT(*)`
+    pub async fn fetch_scalar<T: FromValue>(&self, ctx: &Context) -> Result<T, Error> {
+        let pane = self.fetch_pane(ctx).await?;
+        let row = pane
+            .row_values
+            .first()
+            .ok_or_else(|| Error::QueryError(format!("no rows returned by: {}", self.sql)))?;
+        column(row, 0)
+    }
+
+    /// the customers with the most children
+    pub fn top_by_children(limit: u64) -> Self {
+        Query::new(
+            "SELECT eq_id, full_name,
+                (SELECT COUNT(*) FROM customer t1 WHERE t1.parent_eq_id = customer.eq_id) AS children
+            FROM customer
+            ORDER BY children DESC
+            LIMIT ?",
+        )
+        .arg(limit)
+    }
+
+    /// The top-level customers, which has no parent or whose parent is not in the data,
+    /// the ones with the most children first. `NOT EXISTS` rather than `NOT IN`,
+    /// which is never true once a record has no eq_id.
+    pub fn roots(limit: u64) -> Self {
+        Query::new(
+            "SELECT eq_id, full_name,
+                (SELECT COUNT(*) FROM customer t1 WHERE t1.parent_eq_id = customer.eq_id) AS children
+            FROM customer
+            WHERE customer.parent_eq_id IS NULL
+                OR NOT EXISTS (SELECT 1 FROM customer p WHERE p.eq_id = customer.parent_eq_id)
+            ORDER BY children DESC
+            LIMIT ?",
+        )
+        .arg(limit)
+    }
+
+    /// the children of `eq_id`, the ones with the most children first
+    pub fn children(eq_id: u64, limit: u64) -> Self {
+        Query::new(
+            "SELECT eq_id, full_name,
+                (SELECT COUNT(*) FROM customer t1 WHERE t1.parent_eq_id = customer.eq_id) AS children
+            FROM customer
+            WHERE customer.parent_eq_id = ?
+            ORDER BY children DESC
+            LIMIT ?",
+        )
+        .arg(eq_id)
+        .arg(limit)
+    }
+
+    /// the customers sponsored by `eq_id`, the ones with the most children first
+    pub fn sponsored(eq_id: u64, limit: u64) -> Self {
+        Query::new(
+            "SELECT eq_id, full_name,
+                (SELECT COUNT(*) FROM customer t1 WHERE t1.parent_eq_id = customer.eq_id) AS children
+            FROM customer
+            WHERE customer.sponsor_eq_id = ?
+            ORDER BY children DESC
+            LIMIT ?",
+        )
+        .arg(eq_id)
+        .arg(limit)
+    }
+
+    /// the number of customers sponsored by `eq_id`
+    pub fn sponsored_count(eq_id: u64) -> Self {
+        Query::new("SELECT COUNT(*) AS count FROM customer WHERE customer.sponsor_eq_id = ?").arg(eq_id)
+    }
+}
+
+impl Param {
+    /// the sql literal of the value, with the quotes of a text doubled
+    fn to_literal(&self) -> Result<String, Error> {
+        Ok(match self {
+            Param::Null => "NULL".to_string(),
+            Param::U64(v) => v.to_string(),
+            Param::I64(v) => v.to_string(),
+            Param::F64(v) if v.is_finite() => format!("{v:?}"),
+            Param::F64(v) => return Err(Error::QueryError(format!("{v} is not a number which can be written in sql"))),
+            Param::Text(v) => format!("'{}'", v.replace('\'', "''")),
+        })
+    }
+}
+
+impl From<u64> for Param {
+    fn from(v: u64) -> Self {
+        Param::U64(v)
+    }
+}
+
+impl From<i64> for Param {
+    fn from(v: i64) -> Self {
+        Param::I64(v)
+    }
+}
+
+impl From<f64> for Param {
+    fn from(v: f64) -> Self {
+        Param::F64(v)
+    }
+}
+
+impl From<&str> for Param {
+    fn from(v: &str) -> Self {
+        Param::Text(v.to_string())
+    }
+}
+
+impl From<String> for Param {
+    fn from(v: String) -> Self {
+        Param::Text(v)
+    }
+}
+
+impl<T: Into<Param>> From<Option<T>> for Param {
+    fn from(v: Option<T>) -> Self {
+        v.map(Into::into).unwrap_or(Param::Null)
+    }
+}
+
+/// the integer of any of the integer values
+fn integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::S8(v) => Some(*v as i128),
+        Value::S16(v) => Some(*v as i128),
+        Value::S32(v) => Some(*v as i128),
+        Value::S64(v) => Some(*v as i128),
+        Value::U8(v) => Some(*v as i128),
+        Value::U16(v) => Some(*v as i128),
+        Value::U32(v) => Some(*v as i128),
+        Value::U64(v) => Some(*v as i128),
+        _ => None,
+    }
+}
+
+impl FromValue for u64 {
+    const EXPECTED: &'static str = "an unsigned integer";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        integer(value).and_then(|v| u64::try_from(v).ok())
+    }
+}
+
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        integer(value).and_then(|v| i64::try_from(v).ok())
+    }
+}
+
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::F32(v) => Some(*v as f64),
+            Value::F64(v) => Some(*v),
+            _ => integer(value).map(|v| v as f64),
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a text";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Text(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// a null is `None`, any other value has to be a `T`
+impl<T: FromValue> FromValue for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// read the value at `index` of the row
+pub fn column<T: FromValue>(row: &[Value], index: usize) -> Result<T, Error> {
+    let value = row
+        .get(index)
+        .ok_or_else(|| Error::QueryError(format!("the row has no column {index}, it has {}", row.len())))?;
+    T::from_value(value).ok_or_else(|| {
+        Error::QueryError(format!("column {index} is expected to be {}, found {value:?}", T::EXPECTED))
+    })
+}
+
+macro_rules! tuple_from_row {
+    ($($t:ident: $i:tt),+) => {
+        impl<$($t: FromValue),+> FromRow for ($($t,)+) {
+            fn from_row(row: &[Value]) -> Result<Self, Error> {
+                Ok(($(column::<$t>(row, $i)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(A: 0);
+tuple_from_row!(A: 0, B: 1);
+tuple_from_row!(A: 0, B: 1, C: 2);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3);
+
+impl FromRow for CustomerCount {
+    fn from_row(row: &[Value]) -> Result<Self, Error> {
+        Ok(CustomerCount {
+            eq_id: column(row, 0)?,
+            full_name: column(row, 1)?,
+            children: column(row, 2)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_written_as_literals() {
+        let query = Query::new("SELECT ? AS n FROM customer WHERE eq_id = ? AND full_name = ? AND")
+            .arg(None::<u64>)
+            .arg(42u64)
+            .arg("O'Brien; DROP TABLE customer")
+            .is_null("sponsor_eq_id");
+        assert_eq!(
+            query.to_sql().unwrap(),
+            "SELECT NULL AS n FROM customer WHERE eq_id = 42 AND full_name = 'O''Brien; DROP TABLE customer' AND sponsor_eq_id IS NULL"
+        );
+        // a question mark in a text or a quoted identifier is not a placeholder
+        let query = Query::new("SELECT '?' AS \"what?\", `why?` AS n, ? AS m FROM t").arg(-1i64);
+        assert_eq!(query.to_sql().unwrap(), "SELECT '?' AS \"what?\", `why?` AS n, -1 AS m FROM t");
+        // nor in a comment
+        let query = Query::new("SELECT ? -- why?\n /* and? */ AS n").arg(1u64);
+        assert_eq!(query.to_sql().unwrap(), "SELECT 1 -- why?\n /* and? */ AS n");
+    }
+
+    #[test]
+    fn non_finite_number() {
+        assert_eq!(Query::new("SELECT ?").arg(0.5).to_sql().unwrap(), "SELECT 0.5");
+        assert!(Query::new("SELECT ?").arg(f64::NAN).to_sql().is_err());
+        assert!(Query::new("SELECT ?").arg(f64::INFINITY).to_sql().is_err());
+    }
+
+    #[test]
+    fn param_count_mismatch() {
+        assert!(Query::new("SELECT ?").to_sql().is_err());
+        assert!(Query::new("SELECT 1").arg(1u64).to_sql().is_err());
+    }
+
+    #[test]
+    fn typed_values() {
+        let row = vec![Value::S64(7), Value::Text("Root".to_string()), Value::Nil];
+        assert_eq!(<(u64, String)>::from_row(&row).unwrap(), (7, "Root".to_string()));
+        assert_eq!(column::<Option<u64>>(&row, 2).unwrap(), None);
+        assert!(column::<String>(&row, 0).is_err());
+        assert!(column::<u64>(&row, 3).is_err());
+        assert!(column::<u64>(&[Value::S64(-1)], 0).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use csv::StringRecord;
+use serde::Serialize;
+use crate::customer;
+use crate::schema::{Record, Table};
+use crate::Customer;
+use crate::CustomerData;
+use crate::CustomerTree;
+use crate::Error;
+use crate::LoadOptions;
+use crate::Relation;
+use crate::TableRows;
+
+/// A customer which is placed under a different parent or sponsor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Move {
+    pub eq_id: u64,
+    pub relation: Relation,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+}
+
+/// A customer whose record is replaced by a newer one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub eq_id: u64,
+    /// the columns whose value changed
+    pub fields: Vec<&'static str>,
+}
+
+/// What changed in the tree when applying a newer export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeReport {
+    /// the eq_id of the customers which were not in the tree
+    pub inserted: Vec<u64>,
+    pub updated: Vec<Update>,
+    /// the customers whose parent or sponsor changed, they are also in `updated`
+    pub moved: Vec<Move>,
+    /// the eq_id of the records which are not newer than the one in the tree and were left out
+    pub stale: Vec<u64>,
+    /// the number of records which are the same as the one in the tree
+    pub unchanged: usize,
+    /// the number of records without an eq_id, which can not be applied
+    pub skipped: usize,
+}
+
+impl Customer {
+    /// the value of each column, as written in the table, null is `None`
+    fn field_values(&self) -> Vec<(&'static str, Option<String>)> {
+        let mut record = StringRecord::new();
+        self.write_fields(&mut record);
+        Customer::COLUMNS
+            .iter()
+            .zip(record.iter())
+            .map(|(column, value)| (column.name, (!value.is_empty()).then(|| value.to_string())))
+            .collect()
+    }
+
+    /// the columns whose value differs in the `other` record, with the value before and after
+    pub(crate) fn changes(&self, other: &Customer) -> Vec<(&'static str, Option<String>, Option<String>)> {
+        self.field_values()
+            .into_iter()
+            .zip(other.field_values())
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((field, before), (_, after))| (field, before, after))
+            .collect()
+    }
+
+    /// the names of the columns whose value differs from the `other` record
+    pub fn changed_fields(&self, other: &Customer) -> Vec<&'static str> {
+        self.changes(other).into_iter().map(|(field, _, _)| field).collect()
+    }
+
+    /// the parent and sponsor which are different in the `other` record of the customer `eq_id`
+    pub(crate) fn moves(&self, eq_id: u64, other: &Customer) -> Vec<Move> {
+        [
+            (Relation::Parent, self.parent_eq_id, other.parent_eq_id),
+            (Relation::Sponsor, self.sponsor_eq_id, other.sponsor_eq_id),
+        ]
+        .into_iter()
+        .filter(|(_, from, to)| from != to)
+        .map(|(relation, from, to)| Move { eq_id, relation, from, to })
+        .collect()
+    }
+
+    /// A record replaces the current one when its `change_date` is later.
+    /// Without a `change_date` on both, the record is taken as it is the latest export.
+    fn is_newer_than(&self, current: &Customer) -> bool {
+        match (self.change_date, current.change_date) {
+            (Some(change_date), Some(current)) => change_date > current,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => true,
+        }
+    }
+}
+
+impl ChangeReport {
+    /// Report the record of `eq_id` against the `current` one and return whether it replaces it:
+    /// a new customer is inserted, a known customer is replaced only
+    /// when the record has a later `change_date` than the current one.
+    fn record(&mut self, eq_id: u64, current: Option<&Customer>, customer: &Customer) -> bool {
+        let Some(current) = current else {
+            self.inserted.push(eq_id);
+            return true;
+        };
+        let fields = current.changed_fields(customer);
+        if fields.is_empty() {
+            self.unchanged += 1;
+            return false;
+        }
+        if !customer.is_newer_than(current) {
+            self.stale.push(eq_id);
+            return false;
+        }
+        self.moved.extend(current.moves(eq_id, customer));
+        self.updated.push(Update { eq_id, fields });
+        true
+    }
+}
+
+/// The records a newer export is applied to, keyed on `eq_id`
+trait Upsert {
+    /// the record of `eq_id` which a newer record replaces
+    fn current(&self, eq_id: u64) -> Option<&Customer>;
+    fn upsert(&mut self, eq_id: u64, customer: Customer);
+}
+
+/// Apply the records of a newer export or a delta file keyed on `eq_id`.
+/// New customers are inserted, a known customer is replaced only
+/// when the record has a later `change_date` than the current one.
+fn apply(records: &mut impl Upsert, customers: impl IntoIterator<Item = Customer>) -> ChangeReport {
+    let mut report = ChangeReport::default();
+    for customer in customers {
+        let Some(eq_id) = customer.eq_id else {
+            report.skipped += 1;
+            continue;
+        };
+        if report.record(eq_id, records.current(eq_id), &customer) {
+            records.upsert(eq_id, customer);
+        }
+    }
+    report
+}
+
+impl Upsert for CustomerTree {
+    fn current(&self, eq_id: u64) -> Option<&Customer> {
+        self.get(eq_id).map(|node| node.customer())
+    }
+
+    fn upsert(&mut self, eq_id: u64, customer: Customer) {
+        CustomerTree::upsert(self, eq_id, customer);
+    }
+}
+
+/// The records of an export, with its table rows. Every record is kept: the records without an eq_id
+/// and the repeated ones are left as they are, a newer record replaces the first record of its eq_id,
+/// which is the one the tree keeps, and only the row of a replaced or added record is written again.
+struct Export<'a> {
+    records: &'a mut Vec<Customer>,
+    rows: &'a mut TableRows,
+    options: &'a LoadOptions,
+    /// the index of the first record of each eq_id
+    first: HashMap<u64, usize>,
+}
+
+impl<'a> Export<'a> {
+    fn new(records: &'a mut Vec<Customer>, rows: &'a mut TableRows, options: &'a LoadOptions) -> Self {
+        let mut first: HashMap<u64, usize> = HashMap::new();
+        for (index, record) in records.iter().enumerate() {
+            if let Some(eq_id) = record.eq_id {
+                first.entry(eq_id).or_insert(index);
+            }
+        }
+        Export { records, rows, options, first }
+    }
+}
+
+impl Upsert for Export<'_> {
+    fn current(&self, eq_id: u64) -> Option<&Customer> {
+        self.first.get(&eq_id).map(|index| &self.records[*index])
+    }
+
+    fn upsert(&mut self, eq_id: u64, customer: Customer) {
+        let row = TableRows::row(&customer, self.options);
+        match self.first.get(&eq_id) {
+            Some(&index) => {
+                self.records[index] = customer;
+                self.rows.rows[index] = row;
+            }
+            None => {
+                self.first.insert(eq_id, self.records.len());
+                self.records.push(customer);
+                self.rows.rows.push(row);
+            }
+        }
+    }
+}
+
+impl CustomerTree {
+    /// Apply the records of a newer export or a delta file keyed on `eq_id`.
+    /// New customers are inserted, a known customer is replaced only
+    /// when the record has a later `change_date` than the one in the tree.
+    pub fn apply(&mut self, customers: impl IntoIterator<Item = Customer>) -> ChangeReport {
+        apply(self, customers)
+    }
+
+    /// apply the records of a newer csv or gzipped csv content read with the given options
+    pub fn apply_reader(&mut self, reader: impl Read, options: &LoadOptions) -> Result<ChangeReport, Error> {
+        let parsed = customer::parse_customers(reader, options)?;
+        Ok(self.apply(parsed.customers))
+    }
+
+    /// apply the records of a newer csv or gzipped csv file at `path` read with the given options
+    pub fn apply_path(&mut self, path: impl AsRef<Path>, options: &LoadOptions) -> Result<ChangeReport, Error> {
+        self.apply_reader(File::open(path)?, options)
+    }
+}
+
+impl TableRows {
+    /// Apply the records of a newer export or a delta file to the `records` the rows were written from,
+    /// the same way as [`CustomerTree::apply`], and write again the rows of the records which were replaced or added.
+    pub fn apply(
+        &mut self,
+        records: &mut Vec<Customer>,
+        customers: impl IntoIterator<Item = Customer>,
+        options: &LoadOptions,
+    ) -> ChangeReport {
+        apply(&mut Export::new(records, self, options), customers)
+    }
+}
+
+impl CustomerData {
+    /// Apply the records of a newer export or a delta file to the loaded records, the customer table
+    /// is built again when something changed, with only the rows of the changed records written again.
+    pub fn apply(&mut self, customers: impl IntoIterator<Item = Customer>) -> Result<ChangeReport, Error> {
+        let report = self.rows.apply(&mut self.customers, customers, &self.options);
+        if !report.is_empty() {
+            self.data_source = self.rows.data_source()?;
+        }
+        Ok(report)
+    }
+
+    /// apply the records of a newer csv or gzipped csv file at `path`, read with the options of the loaded data
+    pub fn apply_path(&mut self, path: impl AsRef<Path>) -> Result<ChangeReport, Error> {
+        let parsed = customer::parse_customers(File::open(path)?, &self.options)?;
+        self.apply(parsed.customers)
+    }
+}
+
+impl ChangeReport {
+    /// whether no customer was inserted or updated
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty()
+    }
+}
+
+impl fmt::Display for ChangeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let eq_id = |eq_id: Option<u64>| eq_id.map(|eq_id| eq_id.to_string()).unwrap_or("none".to_string());
+        for inserted in self.inserted.iter() {
+            writeln!(f, "inserted {inserted}")?;
+        }
+        for update in self.updated.iter() {
+            writeln!(f, "updated {}: {}", update.eq_id, update.fields.join(", "))?;
+        }
+        for moved in self.moved.iter() {
+            writeln!(
+                f,
+                "moved {} from {} {} to {}",
+                moved.eq_id,
+                moved.relation,
+                eq_id(moved.from),
+                eq_id(moved.to)
+            )?;
+        }
+        writeln!(
+            f,
+            "{} inserted, {} updated, {} moved, {} unchanged, {} stale, {} skipped",
+            self.inserted.len(),
+            self.updated.len(),
+            self.moved.len(),
+            self.unchanged,
+            self.stale.len(),
+            self.skipped
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::export;
+
+    fn tree(rows: &[&str]) -> CustomerTree {
+        CustomerTree::from_reader(export(rows).as_bytes(), &LoadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn apply_newer_export() {
+        let mut tree = tree(&[
+            r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#,
+            r#"2,1,1,"2020-01-01","2020-01-01","Child A",,,,"#,
+            r#"3,1,2,"2020-01-01","2020-01-01","Child B",,,,"#,
+        ]);
+        let delta = export(&[
+            // the same record
+            r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#,
+            // re-parented and renamed
+            r#"3,1,1,"2020-01-01","2021-05-01","Child B renamed",,,,"#,
+            // an older record
+            r#"2,1,3,"2020-01-01","2019-01-01","Child A",,,,"#,
+            r#"4,3,3,"2021-05-01","2021-05-01","New",,,,"#,
+        ]);
+        let report = tree.apply_reader(delta.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(report.inserted, vec![4]);
+        assert_eq!(
+            report.updated,
+            vec![Update {
+                eq_id: 3,
+                fields: vec!["parent_eq_id", "change_date", "full_name"],
+            }]
+        );
+        assert_eq!(
+            report.moved,
+            vec![Move {
+                eq_id: 3,
+                relation: Relation::Parent,
+                from: Some(2),
+                to: Some(1),
+            }]
+        );
+        assert_eq!(report.stale, vec![2]);
+        assert_eq!(report.unchanged, 1);
+
+        assert_eq!(tree.children(1), &[2, 3]);
+        assert_eq!(tree.children(2), &[] as &[u64]);
+        assert_eq!(tree.children(3), &[4]);
+        assert_eq!(tree.get(3).unwrap().full_name(), "Child B renamed");
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn apply_keeps_every_record_of_the_loaded_data() {
+        let mut data = crate::load(
+            export(&[
+                r#"1,,,"2020-01-01","2020-01-01","Root",,,,"#,
+                r#"2,1,1,"2020-01-01","2020-01-01","Child",,,,"#,
+                // a repeated eq_id and a record without one, the tree leaves them out
+                r#"2,1,1,"2020-01-01","2020-01-01","Child again",,,,"#,
+                r#",1,1,"2020-01-01","2020-01-01","No id",,,,"#,
+            ])
+            .as_bytes(),
+            &LoadOptions::default(),
+        )
+        .await
+        .unwrap();
+        let report = data
+            .apply(vec![
+                Customer {
+                    full_name: "Child renamed".to_string(),
+                    change_date: customer::format_date("2021-01-01", Default::default()),
+                    ..data.customers[1].clone()
+                },
+                Customer {
+                    eq_id: Some(3),
+                    full_name: "New".to_string(),
+                    ..data.customers[0].clone()
+                },
+            ])
+            .unwrap();
+        assert_eq!(report.inserted, vec![3]);
+        assert_eq!(report.updated.len(), 1);
+        let names: Vec<&str> = data.customers.iter().map(|customer| customer.full_name()).collect();
+        assert_eq!(names, vec!["Root", "Child renamed", "Child again", "No id", "New"]);
+        // only the rows of the changed records are written again, they are the same as written from scratch
+        assert_eq!(data.rows.rows, TableRows::new(&data.customers, &data.options).rows);
+    }
+}
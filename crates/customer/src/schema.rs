@@ -0,0 +1,270 @@
+use chrono::{DateTime, Utc};
+use csv::StringRecord;
+use gauntlet::DataSource;
+use crate::customer::{format_date, RecordParser};
+use crate::Error;
+
+/// how a date is written in the csv read by gauntlet, the same as a serialized `NaiveDateTime`
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// A column of a table, derived from a field of the record struct
+#[derive(Debug, Clone, Copy)]
+pub struct Column {
+    pub name: &'static str,
+    /// the type used in the gauntlet csv header, eg: `u64?`
+    pub type_name: &'static str,
+    pub nullable: bool,
+}
+
+/// A record struct whose fields are the columns of a table
+pub trait Table {
+    const COLUMNS: &'static [Column];
+
+    /// the gauntlet csv type header, eg: `{eq_id:u64?,full_name:text}`
+    fn header() -> String {
+        header(Self::COLUMNS)
+    }
+}
+
+/// the gauntlet csv type header of the columns
+fn header(columns: &[Column]) -> String {
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|column| format!("{}:{}", column.name, column.type_name))
+        .collect();
+    format!("{{{}}}", columns.join(","))
+}
+
+/// Parsing a record and writing it to a table, implemented by the `table!` macro
+pub(crate) trait Record: Table + Sized {
+    /// parse the fields in the order of the columns
+    fn from_record(parser: &mut RecordParser) -> Result<Self, Error>;
+
+    /// push the value of each field, in the order of the columns
+    fn write_fields(&self, record: &mut StringRecord);
+
+    /// the rows as a gauntlet table
+    fn data_source(rows: &[Self]) -> Result<DataSource, Error> {
+        let mut table = TableWriter::new(Self::COLUMNS);
+        for row in rows {
+            table.row(|record| row.write_fields(record))?;
+        }
+        table.finish()
+    }
+}
+
+/// Writes the rows of a table in the csv read by `DataSource::from_csv`, the type header
+/// then a record per row. gauntlet only builds a table from a csv, so the parsed records
+/// are written out again, once, into the buffer which is handed to it.
+pub(crate) struct TableWriter {
+    writer: csv::Writer<Vec<u8>>,
+    record: StringRecord,
+}
+
+impl TableWriter {
+    pub(crate) fn new(columns: &[Column]) -> Self {
+        let mut buffer = header(columns).into_bytes();
+        buffer.push(b'\n');
+        TableWriter {
+            writer: csv::WriterBuilder::new().has_headers(false).from_writer(buffer),
+            record: StringRecord::new(),
+        }
+    }
+
+    /// write a row, `fields` pushes the value of each column in order
+    pub(crate) fn row(&mut self, fields: impl FnOnce(&mut StringRecord)) -> Result<(), Error> {
+        self.record.clear();
+        fields(&mut self.record);
+        self.writer.write_record(&self.record)?;
+        Ok(())
+    }
+
+    /// write a row which was pushed beforehand
+    pub(crate) fn write(&mut self, record: &StringRecord) -> Result<(), Error> {
+        self.writer.write_record(record)?;
+        Ok(())
+    }
+
+    /// the header and the rows written so far
+    pub(crate) fn into_csv(self) -> Result<Vec<u8>, Error> {
+        Ok(self.writer.into_inner().map_err(|e| e.into_error())?)
+    }
+
+    pub(crate) fn finish(self) -> Result<DataSource, Error> {
+        Ok(DataSource::from_csv(self.into_csv()?)?)
+    }
+}
+
+/// A type which can be used as a field of a `table!` struct
+pub(crate) trait ColumnValue: Sized {
+    const TYPE_NAME: &'static str;
+    const NULLABLE: bool;
+    fn parse(parser: &mut RecordParser) -> Result<Self, Error>;
+    /// push the value as a csv field, null is empty
+    fn write(&self, record: &mut StringRecord);
+}
+
+impl ColumnValue for Option<u64> {
+    const TYPE_NAME: &'static str = "u64?";
+    const NULLABLE: bool = true;
+
+    fn parse(parser: &mut RecordParser) -> Result<Self, Error> {
+        parser.parse(|v| v.parse().ok(), "an unsigned integer")
+    }
+
+    fn write(&self, record: &mut StringRecord) {
+        match self {
+            Some(value) => record.push_field(&value.to_string()),
+            None => record.push_field(""),
+        }
+    }
+}
+
+impl ColumnValue for f64 {
+    const TYPE_NAME: &'static str = "f64";
+    const NULLABLE: bool = false;
+
+    fn parse(parser: &mut RecordParser) -> Result<Self, Error> {
+        let value: Option<f64> = parser.parse(|v| v.parse().ok(), "a number")?;
+        Ok(value.unwrap_or_default())
+    }
+
+    fn write(&self, record: &mut StringRecord) {
+        record.push_field(&self.to_string());
+    }
+}
+
+impl ColumnValue for Option<DateTime<Utc>> {
+    const TYPE_NAME: &'static str = "utc?";
+    const NULLABLE: bool = true;
+
+    fn parse(parser: &mut RecordParser) -> Result<Self, Error> {
+        let timezone = parser.timezone();
+        parser.parse(
+            |v| format_date(v, timezone),
+            "a date formatted as `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DD` or ISO 8601 with an offset",
+        )
+    }
+
+    fn write(&self, record: &mut StringRecord) {
+        match self {
+            Some(date) => record.push_field(&date.naive_utc().format(DATE_FORMAT).to_string()),
+            None => record.push_field(""),
+        }
+    }
+}
+
+impl ColumnValue for Option<String> {
+    const TYPE_NAME: &'static str = "text?";
+    const NULLABLE: bool = true;
+
+    fn parse(parser: &mut RecordParser) -> Result<Self, Error> {
+        parser.parse(|v| Some(v.to_string()), "a text")
+    }
+
+    fn write(&self, record: &mut StringRecord) {
+        record.push_field(self.as_deref().unwrap_or_default());
+    }
+}
+
+impl ColumnValue for String {
+    const TYPE_NAME: &'static str = "text";
+    const NULLABLE: bool = false;
+
+    fn parse(parser: &mut RecordParser) -> Result<Self, Error> {
+        Ok(parser.text())
+    }
+
+    fn write(&self, record: &mut StringRecord) {
+        record.push_field(self);
+    }
+}
+
+/// Declare a record struct and derive its `Table` columns and `Record` parsing from its fields,
+/// so the parser and the table schema can not drift apart.
+macro_rules! table {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident: $ty:ty,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::schema::Table for $name {
+            const COLUMNS: &'static [$crate::schema::Column] = &[
+                $($crate::schema::Column {
+                    name: stringify!($field),
+                    type_name: <$ty as $crate::schema::ColumnValue>::TYPE_NAME,
+                    nullable: <$ty as $crate::schema::ColumnValue>::NULLABLE,
+                },)*
+            ];
+        }
+
+        impl $crate::schema::Record for $name {
+            fn from_record(parser: &mut $crate::customer::RecordParser) -> Result<Self, $crate::Error> {
+                // the fields are evaluated in the order they are written, which is the column order
+                Ok($name {
+                    $($field: <$ty as $crate::schema::ColumnValue>::parse(parser)?,)*
+                })
+            }
+
+            fn write_fields(&self, record: &mut csv::StringRecord) {
+                $(<$ty as $crate::schema::ColumnValue>::write(&self.$field, record);)*
+            }
+        }
+    };
+}
+
+pub(crate) use table;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customer::{parse_customers, Customer};
+    use crate::LoadOptions;
+
+    /// the header that used to be written by hand, which the gauntlet queries are written against
+    const HEADER: &str = "{eq_id:u64?,sponsor_eq_id:u64?,parent_eq_id:u64?,created_date:utc?,change_date:utc?,full_name:text,invoice_phone_number:text,delivery_phone_number:text,invoice_address:text,shipping_address:text}";
+
+    #[test]
+    fn header_matches_struct() {
+        assert_eq!(Customer::header(), HEADER);
+    }
+
+    #[test]
+    fn export_header_matches_columns() {
+        let sample = include_bytes!("../../../data/customer_sample.csv");
+        let mut rdr = csv::Reader::from_reader(&sample[..]);
+        let headers: Vec<&str> = rdr.headers().unwrap().iter().collect();
+        let columns: Vec<&str> = Customer::COLUMNS.iter().map(|column| column.name).collect();
+        assert_eq!(headers, columns);
+    }
+
+    #[test]
+    fn csv_read_by_gauntlet() {
+        let sample = include_bytes!("../../../data/customer_sample.csv");
+        let customers = parse_customers(&sample[..], &LoadOptions::default()).unwrap().customers;
+        let mut table = TableWriter::new(Customer::COLUMNS);
+        for customer in customers[..2].iter() {
+            table.row(|record| customer.write_fields(record)).unwrap();
+        }
+        let csv = String::from_utf8(table.into_csv().unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], HEADER);
+        assert_eq!(
+            lines[1],
+            "11810572,20543,20543,2021-09-29T16:14:40,2023-03-23T00:31:38,Test12 Test12,+48849823172,+48849823172,\"test 1, Test, 12352, PL\",\"test 1, Test, 12352, PL\""
+        );
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::fs::File;
+use crate::customer::{self, Customer};
+use crate::Error;
+use crate::LoadOptions;
+
+/// An in-memory index of the customers keyed by `eq_id`,
+/// with the adjacency lists of the `parent_eq_id` and `sponsor_eq_id` edges.
+#[derive(Debug, Default)]
+pub struct CustomerTree {
+    customers: Vec<Customer>,
+    /// eq_id -> position in `customers`
+    index: HashMap<u64, usize>,
+    /// parent_eq_id -> eq_id of the children placed under it
+    children: HashMap<u64, Vec<u64>>,
+    /// sponsor_eq_id -> eq_id of the customers it sponsored
+    sponsored: HashMap<u64, Vec<u64>>,
+}
+
+/// A customer in the tree
+#[derive(Debug, Clone, Copy)]
+pub struct TreeNode<'a> {
+    tree: &'a CustomerTree,
+    customer: &'a Customer,
+    eq_id: u64,
+}
+
+/// build the tree from the customer data embedded in the binary
+#[cfg(feature = "embedded")]
+pub fn customer_tree() -> Result<CustomerTree, Error> {
+    customer_tree_with(&LoadOptions::default())
+}
+
+/// build the tree from the customer data embedded in the binary with the given options
+#[cfg(feature = "embedded")]
+pub fn customer_tree_with(options: &LoadOptions) -> Result<CustomerTree, Error> {
+    CustomerTree::from_reader(customer::embedded_data(), options)
+}
+
+impl CustomerTree {
+    /// Build the index from the parsed customer records.
+    /// Records without an `eq_id` can not be referred to and are skipped,
+    /// when an `eq_id` is repeated only the first record is kept.
+    pub fn from_customers(customers: impl IntoIterator<Item = Customer>) -> Self {
+        let mut tree = CustomerTree::default();
+        for customer in customers {
+            let Some(eq_id) = customer.eq_id else {
+                continue;
+            };
+            if tree.index.contains_key(&eq_id) {
+                log::warn!("skipping duplicate eq_id: {eq_id}");
+                continue;
+            }
+            if let Some(parent_eq_id) = customer.parent_eq_id {
+                tree.children.entry(parent_eq_id).or_default().push(eq_id);
+            }
+            if let Some(sponsor_eq_id) = customer.sponsor_eq_id {
+                tree.sponsored.entry(sponsor_eq_id).or_default().push(eq_id);
+            }
+            tree.index.insert(eq_id, tree.customers.len());
+            tree.customers.push(customer);
+        }
+        tree
+    }
+
+    /// build the tree from a csv or gzipped csv content with the given options
+    pub fn from_reader(reader: impl Read, options: &LoadOptions) -> Result<Self, Error> {
+        Ok(Self::from_customers(customer::parse_customers(reader, options)?.customers))
+    }
+
+    /// build the tree from a csv or gzipped csv file at `path` with the given options
+    pub fn from_path(path: impl AsRef<Path>, options: &LoadOptions) -> Result<Self, Error> {
+        Self::from_reader(File::open(path)?, options)
+    }
+
+    /// the number of customers in the tree
+    pub fn len(&self) -> usize {
+        self.customers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.customers.is_empty()
+    }
+
+    pub fn contains(&self, eq_id: u64) -> bool {
+        self.index.contains_key(&eq_id)
+    }
+
+    /// lookup the customer with this `eq_id`
+    pub fn get(&self, eq_id: u64) -> Option<TreeNode<'_>> {
+        self.index.get(&eq_id).map(|&i| TreeNode {
+            tree: self,
+            customer: &self.customers[i],
+            eq_id,
+        })
+    }
+
+    /// all the customers in the order they were loaded
+    pub fn iter(&self) -> impl Iterator<Item = TreeNode<'_>> {
+        self.customers.iter().filter_map(|customer| {
+            let eq_id = customer.eq_id?;
+            Some(TreeNode {
+                tree: self,
+                customer,
+                eq_id,
+            })
+        })
+    }
+
+    /// the eq_id of the customers whose `parent_eq_id` is `eq_id`
+    pub fn children(&self, eq_id: u64) -> &[u64] {
+        self.children.get(&eq_id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// the eq_id of the customers whose `sponsor_eq_id` is `eq_id`
+    pub fn sponsored(&self, eq_id: u64) -> &[u64] {
+        self.sponsored.get(&eq_id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// the customer records in the order they were loaded
+    pub fn customers(&self) -> &[Customer] {
+        &self.customers
+    }
+
+    /// Insert the customer or replace the one with the same `eq_id`, moving it
+    /// to its new parent and sponsor. The replaced record is returned.
+    pub(crate) fn upsert(&mut self, eq_id: u64, customer: Customer) -> Option<Customer> {
+        let Some(&i) = self.index.get(&eq_id) else {
+            if let Some(parent_eq_id) = customer.parent_eq_id {
+                self.children.entry(parent_eq_id).or_default().push(eq_id);
+            }
+            if let Some(sponsor_eq_id) = customer.sponsor_eq_id {
+                self.sponsored.entry(sponsor_eq_id).or_default().push(eq_id);
+            }
+            self.index.insert(eq_id, self.customers.len());
+            self.customers.push(customer);
+            return None;
+        };
+        let previous = std::mem::replace(&mut self.customers[i], customer);
+        let current = &self.customers[i];
+        move_edge(&mut self.children, eq_id, previous.parent_eq_id, current.parent_eq_id);
+        move_edge(&mut self.sponsored, eq_id, previous.sponsor_eq_id, current.sponsor_eq_id);
+        Some(previous)
+    }
+
+    /// the top-level customers, which has no parent or whose parent is not in the tree
+    pub fn roots(&self) -> impl Iterator<Item = TreeNode<'_>> {
+        self.iter().filter(|node| match node.parent_eq_id() {
+            Some(parent_eq_id) => !self.contains(parent_eq_id),
+            None => true,
+        })
+    }
+}
+
+/// move `eq_id` from the adjacency list of `from` to the one of `to`
+fn move_edge(edges: &mut HashMap<u64, Vec<u64>>, eq_id: u64, from: Option<u64>, to: Option<u64>) {
+    if from == to {
+        return;
+    }
+    if let Some(from) = from {
+        if let Some(list) = edges.get_mut(&from) {
+            list.retain(|&e| e != eq_id);
+            if list.is_empty() {
+                edges.remove(&from);
+            }
+        }
+    }
+    if let Some(to) = to {
+        edges.entry(to).or_default().push(eq_id);
+    }
+}
+
+impl<'a> TreeNode<'a> {
+    pub fn eq_id(&self) -> u64 {
+        self.eq_id
+    }
+
+    pub fn parent_eq_id(&self) -> Option<u64> {
+        self.customer.parent_eq_id
+    }
+
+    pub fn sponsor_eq_id(&self) -> Option<u64> {
+        self.customer.sponsor_eq_id
+    }
+
+    pub fn full_name(&self) -> &'a str {
+        &self.customer.full_name
+    }
+
+    /// the full record of this customer
+    pub fn customer(&self) -> &'a Customer {
+        self.customer
+    }
+
+    /// the parent of this customer, if it is in the tree
+    pub fn parent(&self) -> Option<TreeNode<'a>> {
+        self.parent_eq_id().and_then(|eq_id| self.tree.get(eq_id))
+    }
+
+    /// the sponsor of this customer, if it is in the tree
+    pub fn sponsor(&self) -> Option<TreeNode<'a>> {
+        self.sponsor_eq_id().and_then(|eq_id| self.tree.get(eq_id))
+    }
+
+    pub fn children(&self) -> &'a [u64] {
+        self.tree.children(self.eq_id)
+    }
+
+    pub fn sponsored(&self) -> &'a [u64] {
+        self.tree.sponsored(self.eq_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = r#""eq_id","sponsor_eq_id","parent_eq_id","created_date","change_date","full_name","invoice_phone_number","delivery_phone_number","invoice_address","shipping_address"
+1,,,"2020-01-01 00:00:00","2020-01-01 00:00:00","Root",,,,
+2,1,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","Child A",,,,
+3,2,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","Child B",,,,
+4,1,2,"2020-01-01 00:00:00","2020-01-01 00:00:00","Grandchild",,,,
+5,99,99,"2020-01-01 00:00:00","2020-01-01 00:00:00","Orphan",,,,
+"#;
+
+    #[test]
+    fn adjacency() {
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.children(1), &[2, 3]);
+        assert_eq!(tree.sponsored(1), &[2, 4]);
+        assert_eq!(tree.children(4), &[] as &[u64]);
+
+        let node = tree.get(4).unwrap();
+        assert_eq!(node.full_name(), "Grandchild");
+        assert_eq!(node.parent().unwrap().eq_id(), 2);
+        assert_eq!(node.sponsor().unwrap().eq_id(), 1);
+        assert!(tree.get(99).is_none());
+
+        let roots: Vec<u64> = tree.roots().map(|node| node.eq_id()).collect();
+        assert_eq!(roots, vec![1, 5]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use crate::customer::{self, Customer};
+use crate::CustomerData;
+use crate::Error;
+use crate::LoadOptions;
+use crate::Relation;
+
+/// A problem found in the parent/sponsor relationship of the customers.
+/// `record` is the 1-based position of the record in the export, not counting the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// the record has no parsable eq_id, so nothing can refer to it
+    MissingEqId { record: usize },
+    /// the same eq_id is used in more than 1 record
+    DuplicateEqId { eq_id: u64, records: Vec<usize> },
+    /// the customer is its own parent or sponsor
+    SelfReference { eq_id: u64, relation: Relation },
+    /// following the relation from any of these customers leads back to itself
+    Cycle { relation: Relation, eq_ids: Vec<u64> },
+    /// the `parent_eq_id` is not in the export
+    Orphan { eq_id: u64, parent_eq_id: u64 },
+    /// the `sponsor_eq_id` is not in the export
+    DanglingSponsor { eq_id: u64, sponsor_eq_id: u64 },
+}
+
+/// The issues found in an export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+/// validate the customer data embedded in the binary
+#[cfg(feature = "embedded")]
+pub fn validate_customer_data() -> Result<ValidationReport, Error> {
+    ValidationReport::from_reader(customer::embedded_data(), &LoadOptions::default())
+}
+
+impl Issue {
+    /// Cycles, self references and duplicate or missing eq_id makes the tree ambiguous.
+    /// Orphans and dangling sponsors are expected in a partial export where the top-level
+    /// customers points to someone outside of it.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Issue::Orphan { .. } | Issue::DanglingSponsor { .. })
+    }
+}
+
+impl ValidationReport {
+    /// validate the parsed customer records
+    pub fn from_customers(customers: &[Customer]) -> Self {
+        let mut issues = vec![];
+        let mut records: HashMap<u64, Vec<usize>> = HashMap::new();
+        // the first record wins, the same as in the CustomerTree
+        let mut parents: HashMap<u64, Option<u64>> = HashMap::new();
+        let mut sponsors: HashMap<u64, Option<u64>> = HashMap::new();
+        let mut eq_ids = vec![];
+
+        for (i, customer) in customers.iter().enumerate() {
+            let Some(eq_id) = customer.eq_id else {
+                issues.push(Issue::MissingEqId { record: i + 1 });
+                continue;
+            };
+            let positions = records.entry(eq_id).or_default();
+            positions.push(i + 1);
+            if positions.len() == 1 {
+                eq_ids.push(eq_id);
+                parents.insert(eq_id, customer.parent_eq_id);
+                sponsors.insert(eq_id, customer.sponsor_eq_id);
+            }
+        }
+
+        for eq_id in eq_ids.iter() {
+            let positions = &records[eq_id];
+            if positions.len() > 1 {
+                issues.push(Issue::DuplicateEqId {
+                    eq_id: *eq_id,
+                    records: positions.clone(),
+                });
+            }
+        }
+
+        for (relation, above) in [(Relation::Parent, &parents), (Relation::Sponsor, &sponsors)] {
+            for eq_id in eq_ids.iter() {
+                let Some(above_eq_id) = above[eq_id] else {
+                    continue;
+                };
+                if above_eq_id == *eq_id {
+                    issues.push(Issue::SelfReference {
+                        eq_id: *eq_id,
+                        relation,
+                    });
+                } else if !above.contains_key(&above_eq_id) {
+                    issues.push(match relation {
+                        Relation::Parent => Issue::Orphan {
+                            eq_id: *eq_id,
+                            parent_eq_id: above_eq_id,
+                        },
+                        Relation::Sponsor => Issue::DanglingSponsor {
+                            eq_id: *eq_id,
+                            sponsor_eq_id: above_eq_id,
+                        },
+                    });
+                }
+            }
+            issues.extend(find_cycles(&eq_ids, above).into_iter().map(|eq_ids| Issue::Cycle {
+                relation,
+                eq_ids,
+            }));
+        }
+
+        ValidationReport { issues }
+    }
+
+    /// validate a csv or gzipped csv content read with the given options
+    pub fn from_reader(reader: impl Read, options: &LoadOptions) -> Result<Self, Error> {
+        Ok(Self::from_customers(&customer::parse_customers(reader, options)?.customers))
+    }
+
+    /// validate a csv or gzipped csv file at `path` read with the given options
+    pub fn from_path(path: impl AsRef<Path>, options: &LoadOptions) -> Result<Self, Error> {
+        Self::from_reader(File::open(path)?, options)
+    }
+
+    /// true if there is any issue which is an error
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(Issue::is_error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|issue| issue.is_error())
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|issue| !issue.is_error())
+    }
+}
+
+impl CustomerData {
+    /// validate the loaded records, without parsing the export again
+    pub fn validate(&self) -> ValidationReport {
+        ValidationReport::from_customers(&self.customers)
+    }
+}
+
+/// Each customer has at most 1 node above it, so walking up from every customer
+/// either ends outside of the export or runs into a node seen in the same walk, which closes a cycle.
+/// Self references are excluded since they are reported on their own.
+fn find_cycles(eq_ids: &[u64], above: &HashMap<u64, Option<u64>>) -> Vec<Vec<u64>> {
+    let mut cycles = vec![];
+    // eq_id -> the walk which first visited it
+    let mut visited: HashMap<u64, usize> = HashMap::new();
+    for (walk, start) in eq_ids.iter().enumerate() {
+        let mut path = vec![];
+        let mut current = Some(*start);
+        while let Some(eq_id) = current {
+            if let Some(&seen_in) = visited.get(&eq_id) {
+                if seen_in == walk {
+                    let position = path.iter().position(|p| *p == eq_id).expect("must be in path");
+                    let cycle = path.split_off(position);
+                    if cycle.len() > 1 {
+                        cycles.push(cycle);
+                    }
+                }
+                break;
+            }
+            if !above.contains_key(&eq_id) {
+                break;
+            }
+            visited.insert(eq_id, walk);
+            path.push(eq_id);
+            current = above[&eq_id];
+        }
+    }
+    cycles
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::MissingEqId { record } => write!(f, "record {record} has no eq_id"),
+            Issue::DuplicateEqId { eq_id, records } => {
+                write!(f, "eq_id {eq_id} is repeated in records {records:?}")
+            }
+            Issue::SelfReference { eq_id, relation } => write!(f, "{eq_id} is its own {relation}"),
+            Issue::Cycle { relation, eq_ids } => write!(f, "{relation} cycle: {eq_ids:?}"),
+            Issue::Orphan { eq_id, parent_eq_id } => {
+                write!(f, "{eq_id} has a parent {parent_eq_id} which is not in the export")
+            }
+            Issue::DanglingSponsor { eq_id, sponsor_eq_id } => {
+                write!(f, "{eq_id} has a sponsor {sponsor_eq_id} which is not in the export")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors = self.errors().count();
+        let warnings = self.warnings().count();
+        writeln!(f, "{errors} error(s), {warnings} warning(s)")?;
+        for issue in self.errors() {
+            writeln!(f, "error: {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::export;
+
+    fn report(rows: &[&str]) -> ValidationReport {
+        ValidationReport::from_reader(export(rows).as_bytes(), &LoadOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn sample_has_no_errors() {
+        let sample = include_bytes!("../../../data/customer_sample.csv");
+        let report = ValidationReport::from_reader(&sample[..], &LoadOptions::default()).unwrap();
+        assert!(!report.has_errors(), "{report}");
+        assert!(report.issues.contains(&Issue::Orphan {
+            eq_id: 11810572,
+            parent_eq_id: 20543
+        }));
+    }
+
+    #[test]
+    fn detects_issues() {
+        let report = report(&[
+            "1,,,,,Root,,,,",
+            "2,1,2,,,Self parent,,,,",
+            "3,1,4,,,Cycle A,,,,",
+            "4,1,3,,,Cycle B,,,,",
+            "5,77,1,,,Dangling sponsor,,,,",
+            "1,,,,,Duplicate,,,,",
+            "x,,,,,No id,,,,",
+        ]);
+        assert_eq!(
+            report.issues,
+            vec![
+                Issue::MissingEqId { record: 7 },
+                Issue::DuplicateEqId {
+                    eq_id: 1,
+                    records: vec![1, 6]
+                },
+                Issue::SelfReference {
+                    eq_id: 2,
+                    relation: Relation::Parent
+                },
+                Issue::Cycle {
+                    relation: Relation::Parent,
+                    eq_ids: vec![3, 4]
+                },
+                Issue::DanglingSponsor {
+                    eq_id: 5,
+                    sponsor_eq_id: 77
+                },
+            ]
+        );
+        assert!(report.has_errors());
+    }
+
+    #[tokio::test]
+    async fn loaded_data_keeps_the_repeated_records() {
+        let rows = ["1,,,,,Root,,,,", "1,,,,,Duplicate,,,,"];
+        let data = crate::load(export(&rows).as_bytes(), &LoadOptions::default()).await.unwrap();
+        assert_eq!(data.customers.len(), 2);
+        assert_eq!(data.validate(), report(&rows));
+    }
+}
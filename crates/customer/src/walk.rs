@@ -0,0 +1,243 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::collections::VecDeque;
+use serde::Serialize;
+use crate::CustomerTree;
+use crate::TreeNode;
+
+/// Which edge to follow when walking the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    /// the placement tree, via `parent_eq_id`
+    #[default]
+    Parent,
+    /// the sponsorship tree, via `sponsor_eq_id`
+    Sponsor,
+}
+
+/// The order the descendants are visited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// level by level, all the direct children first
+    #[default]
+    BreadthFirst,
+    /// each child is followed by its whole downline before the next sibling
+    DepthFirst,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DownlineOptions {
+    pub relation: Relation,
+    pub order: Order,
+    /// do not go deeper than this level, the direct children are at depth 1
+    pub max_depth: Option<usize>,
+    /// stop after visiting this many descendants
+    pub max_nodes: Option<usize>,
+}
+
+/// A descendant visited in the downline walk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownlineEntry {
+    pub eq_id: u64,
+    /// the distance from the starting customer, the direct children are at depth 1
+    pub depth: usize,
+    /// the eq_id from the starting customer down to this customer, both inclusive
+    pub path: Vec<u64>,
+}
+
+impl CustomerTree {
+    /// the eq_id of the customers directly below `eq_id` in the `relation` tree
+    pub fn adjacent(&self, eq_id: u64, relation: Relation) -> &[u64] {
+        match relation {
+            Relation::Parent => self.children(eq_id),
+            Relation::Sponsor => self.sponsored(eq_id),
+        }
+    }
+
+    /// Walk all the descendants of `eq_id`.
+    /// The starting customer is not part of the result and does not need to be in the tree,
+    /// a customer already visited is not walked again so a cycle in the data will not loop forever.
+    pub fn downline(&self, eq_id: u64, options: &DownlineOptions) -> Vec<DownlineEntry> {
+        let mut result = vec![];
+        let mut visited = HashSet::from([eq_id]);
+        let mut pending = VecDeque::from([(eq_id, vec![eq_id])]);
+        let max_nodes = options.max_nodes.unwrap_or(usize::MAX);
+
+        while result.len() < max_nodes {
+            let next = match options.order {
+                Order::BreadthFirst => pending.pop_front(),
+                Order::DepthFirst => pending.pop_back(),
+            };
+            let Some((current, path)) = next else {
+                break;
+            };
+            let depth = path.len() - 1;
+            if current != eq_id {
+                result.push(DownlineEntry {
+                    eq_id: current,
+                    depth,
+                    path: path.clone(),
+                });
+            }
+            if options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+            let adjacent = self.adjacent(current, options.relation);
+            let unvisited: Vec<u64> = adjacent
+                .iter()
+                .copied()
+                .filter(|child| visited.insert(*child))
+                .collect();
+            let children = unvisited.into_iter().map(|child| {
+                let mut child_path = path.clone();
+                child_path.push(child);
+                (child, child_path)
+            });
+            // the stack pops from the back, so the children are pushed in reverse
+            // to still visit the siblings in their loaded order
+            match options.order {
+                Order::BreadthFirst => pending.extend(children),
+                Order::DepthFirst => pending.extend(children.rev()),
+            }
+        }
+        result
+    }
+
+    /// The chain of `parent_eq_id` ancestors of `eq_id`, starting from its direct parent up to the top-level customer.
+    pub fn upline(&self, eq_id: u64) -> Vec<u64> {
+        self.upline_by(eq_id, Relation::Parent)
+    }
+
+    /// The chain of `sponsor_eq_id` ancestors of `eq_id`, starting from its direct sponsor up to the top-level customer.
+    pub fn sponsor_upline(&self, eq_id: u64) -> Vec<u64> {
+        self.upline_by(eq_id, Relation::Sponsor)
+    }
+
+    /// The chain of ancestors of `eq_id` following the `relation` edge, nearest first.
+    /// The last entry can be an eq_id which is referenced but is not in the tree,
+    /// the walk stops when an ancestor is repeated, which only happens when the data has a cycle.
+    pub fn upline_by(&self, eq_id: u64, relation: Relation) -> Vec<u64> {
+        let mut chain = vec![];
+        let mut visited = HashSet::from([eq_id]);
+        let mut current = self.get(eq_id);
+        while let Some(node) = current {
+            let Some(above) = node.above(relation) else {
+                break;
+            };
+            if !visited.insert(above) {
+                log::warn!("cycle detected in the upline of {eq_id} at {above}");
+                break;
+            }
+            chain.push(above);
+            current = self.get(above);
+        }
+        chain
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Relation::Parent => write!(f, "parent"),
+            Relation::Sponsor => write!(f, "sponsor"),
+        }
+    }
+}
+
+impl FromStr for Relation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parent" => Ok(Relation::Parent),
+            "sponsor" => Ok(Relation::Sponsor),
+            _ => Err(format!("unknown relation `{s}`, expecting `parent` or `sponsor`")),
+        }
+    }
+}
+
+impl TreeNode<'_> {
+    /// the eq_id directly above this customer in the `relation` tree
+    pub fn above(&self, relation: Relation) -> Option<u64> {
+        match relation {
+            Relation::Parent => self.parent_eq_id(),
+            Relation::Sponsor => self.sponsor_eq_id(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoadOptions;
+
+    const CSV: &str = r#""eq_id","sponsor_eq_id","parent_eq_id","created_date","change_date","full_name","invoice_phone_number","delivery_phone_number","invoice_address","shipping_address"
+1,,,"2020-01-01 00:00:00","2020-01-01 00:00:00","Root",,,,
+2,1,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","A",,,,
+3,1,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","B",,,,
+4,1,2,"2020-01-01 00:00:00","2020-01-01 00:00:00","A1",,,,
+5,4,4,"2020-01-01 00:00:00","2020-01-01 00:00:00","A1a",,,,
+6,1,3,"2020-01-01 00:00:00","2020-01-01 00:00:00","B1",,,,
+"#;
+
+    fn eq_ids(entries: &[DownlineEntry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.eq_id).collect()
+    }
+
+    #[test]
+    fn breadth_and_depth_first() {
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        let bfs = tree.downline(1, &DownlineOptions::default());
+        assert_eq!(eq_ids(&bfs), vec![2, 3, 4, 6, 5]);
+        assert_eq!(bfs[4].depth, 3);
+        assert_eq!(bfs[4].path, vec![1, 2, 4, 5]);
+
+        let dfs = tree.downline(1, &DownlineOptions {
+            order: Order::DepthFirst,
+            ..Default::default()
+        });
+        assert_eq!(eq_ids(&dfs), vec![2, 4, 5, 3, 6]);
+    }
+
+    #[test]
+    fn limits_and_relation() {
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        let shallow = tree.downline(1, &DownlineOptions {
+            max_depth: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(eq_ids(&shallow), vec![2, 3]);
+
+        let limited = tree.downline(1, &DownlineOptions {
+            max_nodes: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(eq_ids(&limited), vec![2, 3, 4]);
+
+        let sponsored = tree.downline(1, &DownlineOptions {
+            relation: Relation::Sponsor,
+            ..Default::default()
+        });
+        assert_eq!(eq_ids(&sponsored), vec![2, 3, 4, 6, 5]);
+        assert!(sponsored.iter().all(|entry| entry.eq_id == 5 || entry.depth == 1));
+    }
+
+    #[test]
+    fn upline() {
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(tree.upline(5), vec![4, 2, 1]);
+        assert_eq!(tree.sponsor_upline(5), vec![4, 1]);
+        assert_eq!(tree.upline(1), vec![] as Vec<u64>);
+        assert_eq!(tree.upline(99), vec![] as Vec<u64>);
+    }
+
+    #[test]
+    fn upline_stops_on_cycle() {
+        let csv = CSV.replace("\n1,,,", "\n1,,5,");
+        let tree = CustomerTree::from_reader(csv.as_bytes(), &LoadOptions::default()).unwrap();
+        assert_eq!(tree.upline(5), vec![4, 2, 1]);
+        assert_eq!(tree.upline(1), vec![5, 4, 2]);
+    }
+}
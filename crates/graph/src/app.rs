@@ -1,8 +1,58 @@
 use sauron::*;
+use crate::chart::{polar, truncate, Chart, ChartNode, Layout, BOX_HEIGHT, BOX_WIDTH, SUNBURST_RING};
+
+/// the longest name which fits in a box
+const NAME_LENGTH: usize = 22;
+/// the strip below the chart where the legend is drawn
+const LEGEND_HEIGHT: f64 = 36.0;
+const LEGEND_WIDTH: f64 = 340.0;
+pub(crate) const PARENT_COLOR: &str = "#8a8a8a";
+const SPONSOR_COLOR: &str = "#d9822b";
 
 pub enum Msg{
 }
+
+/// Draws the org chart of a subtree
 pub struct App{
+    chart: Chart,
+}
+
+impl App {
+    pub fn new(chart: Chart) -> Self {
+        App { chart }
+    }
+}
+
+impl App {
+    /// the placement edges and the customers, the sponsorships are drawn over them
+    fn view_layout(&self) -> Node<Msg> {
+        let chart = &self.chart;
+        match chart.layout {
+            Layout::Tree => node!{
+                <g>
+                  <g fill="none" stroke={PARENT_COLOR} stroke-width="1.5">
+                    {for (parent, child) in chart.edges() { edge(parent, child) }}
+                  </g>
+                  {for node in chart.nodes.iter() { customer_box(node) }}
+                </g>
+            },
+            Layout::Radial => node!{
+                <g>
+                  <g stroke={PARENT_COLOR} stroke-width="1.5">
+                    {for (parent, child) in chart.edges() {
+                        node!{<line x1={parent.x.to_string()} y1={parent.y.to_string()} x2={child.x.to_string()} y2={child.y.to_string()} />}
+                    }}
+                  </g>
+                  {for node in chart.nodes.iter() { radial_node(node) }}
+                </g>
+            },
+            Layout::Sunburst => node!{
+                <g stroke="white" stroke-width="1">
+                  {for node in chart.nodes.iter() { sunburst_arc(chart, node) }}
+                </g>
+            },
+        }
+    }
 }
 
 impl Application for App{
@@ -13,10 +63,185 @@ impl Application for App{
     }
 
     fn view(&self) -> Node<Msg> {
+        let chart = &self.chart;
+        let width = chart.width.max(LEGEND_WIDTH);
+        let height = chart.height + LEGEND_HEIGHT;
         node!{
-            <svg viewBox="-100 -100 200 200" xmlns="http://www.w3.org/2000/svg">
-              <circle cx="50" cy="50" r="4" fill="lightblue" stroke="blue" />
+            <svg xmlns="http://www.w3.org/2000/svg"
+                width={width.to_string()}
+                height={height.to_string()}
+                viewBox={format!("0 0 {width} {height}")}
+                font-family="sans-serif">
+              <defs>
+                <marker id="sponsor-arrow" viewBox="0 0 10 10" refX="9" refY="5"
+                    markerWidth="7" markerHeight="7" orient="auto-start-reverse">
+                  <path d="M0,0 L10,5 L0,10 z" fill={SPONSOR_COLOR} />
+                </marker>
+              </defs>
+              <rect width="100%" height="100%" fill="white" />
+              {self.view_layout()}
+              // over the customers, a sponsorship often crosses to another leg of the tree
+              <g fill="none" stroke={SPONSOR_COLOR} stroke-width="1.5" stroke-dasharray="6 4"
+                  marker-end="url(#sponsor-arrow)">
+                {for (sponsor, sponsored) in chart.sponsor_edges() { sponsor_arc(chart, sponsor, sponsored) }}
+              </g>
+              {legend(chart.height, chart.layout != Layout::Sunburst)}
             </svg>
         }
     }
 }
+
+/// an elbow from the bottom of the parent to the top of the child
+pub(crate) fn edge<MSG>(parent: &ChartNode, child: &ChartNode) -> Node<MSG> {
+    let (x1, y1) = parent.bottom();
+    let (x2, y2) = child.top();
+    let middle = (y1 + y2) / 2.0;
+    node!{
+        <path d={format!("M{x1},{y1} V{middle} H{x2} V{y2}")} />
+    }
+}
+
+/// the curve from the sponsor to the sponsored customer, see [`Chart::sponsor_curve`]
+fn sponsor_arc(chart: &Chart, sponsor: &ChartNode, sponsored: &ChartNode) -> Node<Msg> {
+    let ((x1, y1), (cx, cy), (x2, y2)) = chart.sponsor_curve(sponsor, sponsored);
+    node!{
+        <path d={format!("M{x1},{y1} Q{cx},{cy} {x2},{y2}")} />
+    }
+}
+
+/// a dot with the name beside it, away from the center
+fn radial_node(node: &ChartNode) -> Node<Msg> {
+    // the angle from the x axis in degrees, the names on the left half are flipped to not be upside down
+    let degrees = node.angle.0 * 360.0 - 90.0;
+    let (rotate, dx, anchor) = if node.depth == 0 {
+        (0.0, 8.0, "start")
+    } else if node.angle.0 > 0.5 {
+        (degrees + 180.0, -8.0, "end")
+    } else {
+        (degrees, 8.0, "start")
+    };
+    node!{
+        <g transform={format!("translate({},{}) rotate({rotate})", node.x, node.y)}>
+          <title>{text(title(node))}</title>
+          <circle r="4" fill="#eef4fb" stroke="#4a78b5" stroke-width="1.5" />
+          <text x={dx.to_string()} dy="0.35em" font-size="11" text-anchor={anchor}>{text(truncate(&node.full_name, NAME_LENGTH))}</text>
+        </g>
+    }
+}
+
+/// The ring sector of a customer, the color follows the angle so a leg of the tree keeps its hue
+/// and gets lighter with the depth.
+fn sunburst_arc(chart: &Chart, node: &ChartNode) -> Node<Msg> {
+    let (start, end) = node.angle;
+    let hue = (start + end) / 2.0 * 360.0;
+    let lightness = (45.0 + node.depth as f64 * 8.0).min(85.0);
+    let fill = format!("hsl({hue:.0},55%,{lightness:.0}%)");
+    let inner = node.depth as f64 * SUNBURST_RING;
+    let outer = inner + SUNBURST_RING;
+    let shape = if node.depth == 0 {
+        node!{<circle cx={chart.center.0.to_string()} cy={chart.center.1.to_string()} r={outer.to_string()} fill={fill} />}
+    } else {
+        let large = if end - start > 0.5 { 1 } else { 0 };
+        let (ox1, oy1) = polar(chart.center, start, outer);
+        let (ox2, oy2) = polar(chart.center, end, outer);
+        let (ix2, iy2) = polar(chart.center, end, inner);
+        let (ix1, iy1) = polar(chart.center, start, inner);
+        let d = format!(
+            "M{ox1},{oy1} A{outer},{outer} 0 {large} 1 {ox2},{oy2} L{ix2},{iy2} A{inner},{inner} 0 {large} 0 {ix1},{iy1} Z"
+        );
+        node!{<path d={d} fill={fill} />}
+    };
+    // the name only when the arc is long enough to show some of it
+    let room = if node.depth == 0 { outer * 2.0 } else { (end - start) * std::f64::consts::TAU * (inner + outer) / 2.0 };
+    let length = (room / 7.0) as usize;
+    node!{
+        <g>
+          <title>{text(title(node))}</title>
+          {shape}
+          {if length >= 4 {
+              node!{<text x={node.x.to_string()} y={node.y.to_string()} dy="0.35em" font-size="10" text-anchor="middle"
+                  fill="#222222" stroke="none">{text(truncate(&node.full_name, length.min(NAME_LENGTH)))}</text>}
+          } else {
+              node!{<g />}
+          }}
+        </g>
+    }
+}
+
+/// What the lines are. In the sunburst the placement is the ring of a customer inside the arc of its parent,
+/// so there is no line for it.
+fn legend(y: f64, placement_line: bool) -> Node<Msg> {
+    let placement = if placement_line {
+        node!{<line x1="0" y1="0" x2="30" y2="0" stroke={PARENT_COLOR} stroke-width="1.5" />}
+    } else {
+        node!{<rect x="0" y="-6" width="30" height="12" fill="hsl(200,55%,61%)" />}
+    };
+    let placement_label = if placement_line { "placement (parent)" } else { "placement (inside the parent)" };
+    node!{
+        <g transform={format!("translate(20,{})", y + LEGEND_HEIGHT / 2.0)} font-size="12" fill="#333333">
+          {placement}
+          <text x="38" y="4">{text(placement_label)}</text>
+          <line x1="230" y1="0" x2="260" y2="0" stroke={SPONSOR_COLOR} stroke-width="1.5"
+              stroke-dasharray="6 4" marker-end="url(#sponsor-arrow)" />
+          <text x="268" y="4">{text("sponsor")}</text>
+        </g>
+    }
+}
+
+/// the box of a customer with its name, eq_id and number of children
+pub(crate) fn customer_box<MSG>(node: &ChartNode) -> Node<MSG> {
+    let children = match node.child_count {
+        1 => "1 child".to_string(),
+        count => format!("{count} children"),
+    };
+    node!{
+        <g transform={format!("translate({},{})", node.x - BOX_WIDTH / 2.0, node.y - BOX_HEIGHT / 2.0)}>
+          <title>{text(title(node))}</title>
+          <rect width={BOX_WIDTH.to_string()} height={BOX_HEIGHT.to_string()} rx="6" fill="#eef4fb" stroke="#4a78b5" />
+          <text x="10" y="20" font-size="13" font-weight="bold">{text(truncate(&node.full_name, NAME_LENGTH))}</text>
+          <text x="10" y="38" font-size="11" fill="#555555">{text(format!("#{} · {children}", node.eq_id))}</text>
+        </g>
+    }
+}
+
+/// the tooltip of a box, with the sponsor even when it is not drawn
+fn title(node: &ChartNode) -> String {
+    match node.sponsor_eq_id {
+        Some(sponsor_eq_id) => format!("{} (#{}), sponsored by #{sponsor_eq_id}", node.full_name, node.eq_id),
+        None => format!("{} (#{})", node.full_name, node.eq_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use customer::{CustomerTree, LoadOptions};
+    use crate::chart::ChartOptions;
+
+    const CSV: &str = r#""eq_id","sponsor_eq_id","parent_eq_id","created_date","change_date","full_name","invoice_phone_number","delivery_phone_number","invoice_address","shipping_address"
+1,,,"2020-01-01 00:00:00","2020-01-01 00:00:00","Root",,,,
+2,1,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","Child A",,,,
+3,2,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","Child B",,,,
+"#;
+
+    fn render(layout: Layout) -> String {
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        let chart = Chart::new(&tree, 1, &ChartOptions { layout, ..Default::default() }).unwrap();
+        App::new(chart).view().render_to_string()
+    }
+
+    #[test]
+    fn dashed_sponsor_arc_and_legend() {
+        let svg = render(Layout::Tree);
+        // 3 is placed under 1 and sponsored by 2, a dashed curve with an arrow from the top of 2 to the top of 3
+        let arcs = r##"<g fill="none" stroke="#d9822b" stroke-width="1.5" stroke-dasharray="6 4" marker-end="url(#sponsor-arrow)"><path d="M"##;
+        let start = svg.find(arcs).expect("the group of the sponsor arcs") + arcs.len() - r#"<path d="M"#.len();
+        let end = start + svg[start..].find("</g>").unwrap();
+        assert_eq!(svg[start..end].matches("<path").count(), 1);
+        assert!(svg[start..end].contains(" Q"));
+        assert!(svg.contains(r#"<text x="38" y="4">placement (parent)</text>"#));
+        assert!(svg.contains(r#"<text x="268" y="4">sponsor</text>"#));
+        // the placement is the ring inside the parent in the sunburst
+        assert!(render(Layout::Sunburst).contains("placement (inside the parent)"));
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use customer::{CustomerTree, DownlineOptions};
+use crate::layout;
+
+/// the size of the box of a customer, in px
+pub const BOX_WIDTH: f64 = 180.0;
+pub const BOX_HEIGHT: f64 = 48.0;
+/// the space between 2 boxes side by side
+const H_GAP: f64 = 20.0;
+/// the space between a parent and its children, where the edges are drawn
+const V_GAP: f64 = 40.0;
+/// the space around the chart
+const MARGIN: f64 = 20.0;
+/// how far at most the middle of a sponsor curve is off the straight line, so a long one stays near its boxes
+const MAX_BEND: f64 = 60.0;
+/// the distance between the levels of the radial tree
+pub const RADIAL_RING: f64 = 110.0;
+/// the room around the radial tree for the names of the outer customers
+const RADIAL_LABEL: f64 = 140.0;
+/// the width of a level of the sunburst, the root is the disc in the middle
+pub const SUNBURST_RING: f64 = 70.0;
+
+/// How the subtree is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Layout {
+    /// top down, the children below their parent
+    #[default]
+    Tree,
+    /// the root in the middle and each level on a circle around it
+    Radial,
+    /// each level is a ring, the arc of a customer is proportional to the size of its downline
+    Sunburst,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChartOptions {
+    /// do not go deeper than this level below the root, the direct children are at depth 1
+    pub max_depth: Option<usize>,
+    /// stop after this many descendants of the root
+    pub max_nodes: Option<usize>,
+    pub layout: Layout,
+}
+
+/// A customer placed in the chart
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartNode {
+    pub eq_id: u64,
+    pub full_name: String,
+    /// all the children in the tree, some of them may not be drawn
+    pub child_count: usize,
+    /// the index of the parent in the chart, the root has none
+    pub parent: Option<usize>,
+    pub sponsor_eq_id: Option<u64>,
+    /// the index of the sponsor in the chart, none when the sponsor is not drawn
+    pub sponsor: Option<usize>,
+    /// the level below the root
+    pub depth: usize,
+    /// the center of the box, of the dot in the radial tree, or of the arc in the sunburst
+    pub x: f64,
+    pub y: f64,
+    /// the start and end of the arc, in turns clockwise from the top, or the angle of the dot in the radial tree
+    pub angle: (f64, f64),
+}
+
+/// The org chart of a subtree, the root first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub layout: Layout,
+    pub nodes: Vec<ChartNode>,
+    pub width: f64,
+    pub height: f64,
+    /// the center of the radial tree and of the sunburst
+    pub center: (f64, f64),
+}
+
+impl ChartNode {
+    /// the middle of the top side of the box, where the edge from the parent ends
+    pub fn top(&self) -> (f64, f64) {
+        (self.x, self.y - BOX_HEIGHT / 2.0)
+    }
+
+    /// the middle of the bottom side of the box, where the edges to the children start
+    pub fn bottom(&self) -> (f64, f64) {
+        (self.x, self.y + BOX_HEIGHT / 2.0)
+    }
+}
+
+impl Chart {
+    /// Lay out the placement subtree of `eq_id`,
+    /// `None` if there is no customer `eq_id`.
+    pub fn new(tree: &CustomerTree, eq_id: u64, options: &ChartOptions) -> Option<Chart> {
+        let root = tree.get(eq_id)?;
+        let downline = tree.downline(
+            eq_id,
+            &DownlineOptions {
+                max_depth: options.max_depth,
+                max_nodes: options.max_nodes,
+                ..Default::default()
+            },
+        );
+
+        let mut nodes = vec![ChartNode {
+            eq_id,
+            full_name: root.full_name().to_string(),
+            child_count: root.children().len(),
+            parent: None,
+            sponsor_eq_id: root.sponsor_eq_id(),
+            sponsor: None,
+            depth: 0,
+            x: 0.0,
+            y: 0.0,
+            angle: (0.0, 0.0),
+        }];
+        let mut index = HashMap::from([(eq_id, 0)]);
+        let mut children: Vec<Vec<usize>> = vec![vec![]];
+        // breadth first, so a parent is always placed before its children and the siblings keep their order
+        for entry in downline {
+            let parent = index[&entry.path[entry.path.len() - 2]];
+            let i = nodes.len();
+            let node = tree.get(entry.eq_id);
+            nodes.push(ChartNode {
+                eq_id: entry.eq_id,
+                full_name: node.map(|node| node.full_name().to_string()).unwrap_or_default(),
+                child_count: tree.children(entry.eq_id).len(),
+                parent: Some(parent),
+                sponsor_eq_id: node.and_then(|node| node.sponsor_eq_id()),
+                sponsor: None,
+                depth: entry.depth,
+                x: 0.0,
+                y: 0.0,
+                angle: (0.0, 0.0),
+            });
+            index.insert(entry.eq_id, i);
+            children.push(vec![]);
+            children[parent].push(i);
+        }
+        // the sponsor can be anywhere in the chart, so it is only known once all the nodes are placed
+        for node in nodes.iter_mut() {
+            node.sponsor = node.sponsor_eq_id.and_then(|sponsor_eq_id| index.get(&sponsor_eq_id).copied());
+        }
+
+        Some(match options.layout {
+            Layout::Tree => Chart::tree(nodes),
+            Layout::Radial => Chart::radial(nodes, &children),
+            Layout::Sunburst => Chart::sunburst(nodes, &children),
+        })
+    }
+
+    /// Lay out top down the nodes linked to their parent, the parents before their children,
+    /// the nodes without a parent are side by side at the top, eg: the customers expanded in the viewer.
+    pub fn tree(mut nodes: Vec<ChartNode>) -> Chart {
+        // a node above the roots, which is not drawn
+        let top = nodes.len();
+        let mut children = vec![vec![]; nodes.len() + 1];
+        for (i, node) in nodes.iter().enumerate() {
+            children[node.parent.unwrap_or(top)].push(i);
+        }
+        let layout = layout::tidy(&children, top, 1.0);
+        for (i, node) in nodes.iter_mut().enumerate() {
+            node.depth = layout.depth[i] - 1;
+            node.x = MARGIN + BOX_WIDTH / 2.0 + layout.x[i] * (BOX_WIDTH + H_GAP);
+            node.y = MARGIN + BOX_HEIGHT / 2.0 + node.depth as f64 * (BOX_HEIGHT + V_GAP);
+        }
+        let slots = layout.x[..top].iter().copied().fold(0.0, f64::max);
+        let max_depth = nodes.iter().map(|node| node.depth).max().unwrap_or(0) as f64;
+        Chart {
+            layout: Layout::Tree,
+            width: 2.0 * MARGIN + BOX_WIDTH + slots * (BOX_WIDTH + H_GAP),
+            height: 2.0 * MARGIN + BOX_HEIGHT + max_depth * (BOX_HEIGHT + V_GAP),
+            nodes,
+            center: (0.0, 0.0),
+        }
+    }
+
+    /// the tidy tree bent around the circle, with a slot left between the last and the first child
+    fn radial(nodes: Vec<ChartNode>, children: &[Vec<usize>]) -> Chart {
+        let layout = layout::tidy(children, 0, 1.0);
+        let slots = layout.x.iter().copied().fold(0.0, f64::max) + 1.0;
+        let mut chart = Chart::square(nodes, Layout::Radial, |max_depth| {
+            2.0 * (max_depth * RADIAL_RING + RADIAL_LABEL)
+        });
+        for (i, node) in chart.nodes.iter_mut().enumerate() {
+            let angle = layout.x[i] / slots;
+            node.angle = (angle, angle);
+            (node.x, node.y) = polar(chart.center, angle, node.depth as f64 * RADIAL_RING);
+        }
+        chart
+    }
+
+    fn sunburst(nodes: Vec<ChartNode>, children: &[Vec<usize>]) -> Chart {
+        let layout = layout::sunburst(children, 0);
+        let mut chart = Chart::square(nodes, Layout::Sunburst, |max_depth| {
+            2.0 * ((max_depth + 1.0) * SUNBURST_RING + MARGIN)
+        });
+        for (i, node) in chart.nodes.iter_mut().enumerate() {
+            node.angle = (layout.start[i], layout.end[i]);
+            // the root is the disc in the middle, the others in the middle of their arc
+            let radius = if node.depth == 0 { 0.0 } else { (node.depth as f64 + 0.5) * SUNBURST_RING };
+            (node.x, node.y) = polar(chart.center, (layout.start[i] + layout.end[i]) / 2.0, radius);
+        }
+        chart
+    }
+
+    /// a square chart around its center, of the size for the depth of the nodes
+    fn square(nodes: Vec<ChartNode>, layout: Layout, size: impl Fn(f64) -> f64) -> Chart {
+        let max_depth = nodes.iter().map(|node| node.depth).max().unwrap_or(0) as f64;
+        let size = size(max_depth);
+        Chart {
+            layout,
+            nodes,
+            width: size,
+            height: size,
+            center: (size / 2.0, size / 2.0),
+        }
+    }
+
+    /// the parent and child of each edge
+    pub fn edges(&self) -> impl Iterator<Item = (&ChartNode, &ChartNode)> {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.parent.map(|parent| (&self.nodes[parent], node)))
+    }
+
+    /// The sponsor and sponsored customer of each sponsorship drawn in the chart.
+    /// A sponsor which is also the parent is already linked by the placement edge, so it is left out.
+    pub fn sponsor_edges(&self) -> impl Iterator<Item = (&ChartNode, &ChartNode)> {
+        self.nodes
+            .iter()
+            .filter_map(|node| match node.sponsor {
+                Some(sponsor) if node.parent != Some(sponsor) => Some((&self.nodes[sponsor], node)),
+                _ => None,
+            })
+    }
+
+    /// The start, control point and end of the quadratic curve from the sponsor to the sponsored customer,
+    /// bent so it is not mistaken for a placement edge. In the tree it is bent to the side, in the radial tree
+    /// and the sunburst toward the center. The control point is in the chart, so the whole curve is.
+    pub fn sponsor_curve(&self, sponsor: &ChartNode, sponsored: &ChartNode) -> ((f64, f64), (f64, f64), (f64, f64)) {
+        match self.layout {
+            Layout::Tree => {
+                // a sponsored customer on the same level or above is reached from the top of the sponsor
+                let (x1, y1) = if sponsored.y <= sponsor.y { sponsor.top() } else { sponsor.bottom() };
+                let (x2, y2) = sponsored.top();
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                // the control point is off the middle, perpendicular to the line, upward or else to the right
+                let (mut nx, mut ny) = (dy, -dx);
+                if ny > 0.0 || (ny == 0.0 && nx < 0.0) {
+                    (nx, ny) = (-nx, -ny);
+                }
+                let scale = (0.25_f64).min(MAX_BEND / dx.hypot(dy).max(1.0));
+                let control = (
+                    ((x1 + x2) / 2.0 + nx * scale).clamp(0.0, self.width),
+                    ((y1 + y2) / 2.0 + ny * scale).clamp(0.0, self.height),
+                );
+                ((x1, y1), control, (x2, y2))
+            }
+            Layout::Radial | Layout::Sunburst => {
+                let (x1, y1, x2, y2) = (sponsor.x, sponsor.y, sponsored.x, sponsored.y);
+                let control = (
+                    ((x1 + x2) / 2.0 + self.center.0) / 2.0,
+                    ((y1 + y2) / 2.0 + self.center.1) / 2.0,
+                );
+                ((x1, y1), control, (x2, y2))
+            }
+        }
+    }
+}
+
+/// the point at `radius` from `center`, at `angle` turns clockwise from the top
+pub fn polar(center: (f64, f64), angle: f64, radius: f64) -> (f64, f64) {
+    let (sin, cos) = (angle * TAU).sin_cos();
+    (center.0 + radius * sin, center.1 - radius * cos)
+}
+
+/// the text cut to fit `max` characters, with an ellipsis when it is cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+        format!("{cut}…")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use customer::LoadOptions;
+
+    const CSV: &str = r#""eq_id","sponsor_eq_id","parent_eq_id","created_date","change_date","full_name","invoice_phone_number","delivery_phone_number","invoice_address","shipping_address"
+1,,,"2020-01-01 00:00:00","2020-01-01 00:00:00","Root",,,,
+2,1,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","Child A",,,,
+3,2,1,"2020-01-01 00:00:00","2020-01-01 00:00:00","Child B",,,,
+4,1,2,"2020-01-01 00:00:00","2020-01-01 00:00:00","Grandchild",,,,
+"#;
+
+    #[test]
+    fn chart_of_a_subtree() {
+        // 1 -> 2, 3; 2 -> 4
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        let chart = Chart::new(&tree, 1, &ChartOptions::default()).unwrap();
+        let eq_ids: Vec<u64> = chart.nodes.iter().map(|node| node.eq_id).collect();
+        assert_eq!(eq_ids, vec![1, 2, 3, 4]);
+        assert_eq!(chart.nodes[0].child_count, 2);
+        assert_eq!(chart.edges().count(), 3);
+        // 3 is placed under 1 but sponsored by 2, 4 is placed under 2 but sponsored by 1
+        let sponsorships: Vec<(u64, u64)> = chart
+            .sponsor_edges()
+            .map(|(sponsor, sponsored)| (sponsor.eq_id, sponsored.eq_id))
+            .collect();
+        assert_eq!(sponsorships, vec![(2, 3), (1, 4)]);
+        // the root is centered over its children, the children are a level below
+        let (root_x, _) = chart.nodes[0].bottom();
+        assert_eq!(root_x, (chart.nodes[1].top().0 + chart.nodes[2].top().0) / 2.0);
+        assert!(chart.nodes[1].y > chart.nodes[0].y);
+        assert!(chart.nodes.iter().all(|node| node.x + BOX_WIDTH / 2.0 <= chart.width));
+
+        let chart = Chart::new(&tree, 1, &ChartOptions { max_depth: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(chart.nodes.len(), 3);
+        // 2 still shows it has a child which is not drawn
+        assert_eq!(chart.nodes[1].child_count, 1);
+        // the sponsor of 3 is drawn, the sponsor of the root is not in the tree
+        assert_eq!(chart.nodes[2].sponsor, Some(1));
+        assert_eq!(chart.nodes[0].sponsor, None);
+        assert!(Chart::new(&tree, 5, &ChartOptions::default()).is_none());
+    }
+
+    #[test]
+    fn roots_side_by_side() {
+        let node = |eq_id, parent| ChartNode {
+            eq_id,
+            full_name: String::new(),
+            child_count: 0,
+            parent,
+            sponsor_eq_id: None,
+            sponsor: None,
+            depth: 0,
+            x: 0.0,
+            y: 0.0,
+            angle: (0.0, 0.0),
+        };
+        let chart = Chart::tree(vec![node(1, None), node(2, None), node(3, Some(1))]);
+        assert_eq!(chart.nodes[0].y, chart.nodes[1].y);
+        assert!(chart.nodes[1].x > chart.nodes[0].x);
+        assert_eq!(chart.nodes[2].depth, 1);
+        assert_eq!(chart.edges().count(), 1);
+    }
+
+    #[test]
+    fn radial_and_sunburst_are_around_the_center() {
+        let tree = CustomerTree::from_reader(CSV.as_bytes(), &LoadOptions::default()).unwrap();
+        for layout in [Layout::Radial, Layout::Sunburst] {
+            let chart = Chart::new(&tree, 1, &ChartOptions { layout, ..Default::default() }).unwrap();
+            assert_eq!((chart.nodes[0].x, chart.nodes[0].y), chart.center);
+            // the deeper, the further from the center
+            let distance = |node: &ChartNode| (node.x - chart.center.0).hypot(node.y - chart.center.1);
+            assert!(distance(&chart.nodes[3]) > distance(&chart.nodes[1]));
+            assert!(chart.nodes.iter().all(|node| node.x >= 0.0 && node.x <= chart.width));
+        }
+        let chart = Chart::new(&tree, 1, &ChartOptions { layout: Layout::Sunburst, ..Default::default() }).unwrap();
+        // 2 has 1 child, so its arc is twice the arc of 3
+        let span = |node: &ChartNode| node.angle.1 - node.angle.0;
+        assert!((span(&chart.nodes[1]) - 2.0 * span(&chart.nodes[2])).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sponsor_curves_stay_in_the_chart() {
+        // 13 is the last of 12 children side by side, sponsored by the first
+        let mut csv = CSV.lines().next().unwrap().to_string();
+        csv.push_str("\n1,,,,,\"Root\",,,,\n");
+        for eq_id in 2..=13 {
+            let sponsor = if eq_id == 13 { 2 } else { 1 };
+            csv.push_str(&format!("{eq_id},{sponsor},1,,,\"Child\",,,,\n"));
+        }
+        let tree = CustomerTree::from_reader(csv.as_bytes(), &LoadOptions::default()).unwrap();
+        for layout in [Layout::Tree, Layout::Radial, Layout::Sunburst] {
+            let chart = Chart::new(&tree, 1, &ChartOptions { layout, ..Default::default() }).unwrap();
+            let (sponsor, sponsored) = chart.sponsor_edges().next().unwrap();
+            let (start, control, end) = chart.sponsor_curve(sponsor, sponsored);
+            assert_eq!(end, if layout == Layout::Tree { sponsored.top() } else { (sponsored.x, sponsored.y) });
+            for (x, y) in [start, control, end] {
+                assert!((0.0..=chart.width).contains(&x) && (0.0..=chart.height).contains(&y), "{layout:?} {x},{y}");
+            }
+        }
+    }
+
+    #[test]
+    fn truncate_long_names() {
+        assert_eq!(truncate("Ann", 5), "Ann");
+        assert_eq!(truncate("Annabelle", 5), "Anna…");
+    }
+}
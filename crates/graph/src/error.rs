@@ -0,0 +1,18 @@
+use thiserror::Error;
+
+/// An error of the png and pdf export
+#[derive(Debug, Error)]
+pub enum Error{
+    #[error("{0}")]
+    SvgError(#[from] resvg::usvg::Error),
+    #[error("{0}")]
+    PngError(#[from] png::EncodingError),
+    /// the chart does not fit in an image of this size
+    #[error("a {width}x{height} px image is too large, lower the dpi or draw fewer customers")]
+    ImageTooLarge { width: u32, height: u32 },
+    #[error("the dpi has to be a positive number, not {0}")]
+    InvalidDpi(f32),
+    /// the pdf of the chart would have more pages than `MAX_PAGES`
+    #[error("the chart takes {pages} pages, raise the dpi or draw fewer customers")]
+    TooManyPages { pages: f32 },
+}
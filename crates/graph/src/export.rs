@@ -0,0 +1,245 @@
+use clap::ValueEnum;
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{self, fontdb, PostProcessingSteps, TreeParsing, TreePostProc};
+use crate::Error;
+
+/// the resolution of the svg, a px is 1/96 inch
+pub const SVG_DPI: f32 = 96.0;
+/// the blank border of a pdf page, in pt
+const PAGE_MARGIN: f32 = 36.0;
+/// the millimeters in a pt, a pt is 1/72 inch
+const MM: f32 = 72.0 / 25.4;
+/// the most pages of a pdf, a lower dpi prints the chart over more pages
+pub const MAX_PAGES: usize = 1000;
+
+/// The paper of the pdf
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum PageSize {
+    #[default]
+    A4,
+    A3,
+    Letter,
+    Legal,
+    Tabloid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportOptions {
+    /// the pixels per inch of the png, and how many px of the chart are printed in an inch of the pdf
+    pub dpi: f32,
+    pub page_size: PageSize,
+    pub landscape: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            dpi: SVG_DPI,
+            page_size: PageSize::default(),
+            landscape: false,
+        }
+    }
+}
+
+impl PageSize {
+    /// the width and height in pt, portrait
+    pub fn size(&self) -> (f32, f32) {
+        let (width, height) = match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::A3 => (297.0, 420.0),
+            PageSize::Letter => (215.9, 279.4),
+            PageSize::Legal => (215.9, 355.6),
+            PageSize::Tabloid => (279.4, 431.8),
+        };
+        (width * MM, height * MM)
+    }
+}
+
+/// The fonts of the system, the text of the chart is left out when there is none.
+/// They are loaded once by the caller, who can tell about an empty database.
+pub fn system_fonts() -> fontdb::Database {
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    // the chart asks for `sans-serif`, which is Arial unless told otherwise, and is left out when Arial is missing
+    let sans_serif = fontdb::Query {
+        families: &[fontdb::Family::SansSerif],
+        ..Default::default()
+    };
+    if fonts.query(&sans_serif).is_none() {
+        let families: Vec<String> = fonts
+            .faces()
+            .filter_map(|face| face.families.first().map(|(family, _)| family.clone()))
+            .collect();
+        let family = families
+            .iter()
+            .find(|family| family.contains("Sans") && !family.contains("Mono"))
+            .or(families.first());
+        if let Some(family) = family {
+            fonts.set_sans_serif_family(family.clone());
+        }
+    }
+    fonts
+}
+
+/// a dpi has to be a positive number, the chart is scaled by it
+pub fn check_dpi(dpi: f32) -> Result<f32, Error> {
+    if dpi.is_finite() && dpi > 0.0 {
+        Ok(dpi)
+    } else {
+        Err(Error::InvalidDpi(dpi))
+    }
+}
+
+/// parse the svg with the text turned into paths of the `fonts`
+fn parse(svg: &str, fonts: &fontdb::Database) -> Result<usvg::Tree, Error> {
+    let mut tree = usvg::Tree::from_str(svg, &usvg::Options::default())?;
+    tree.postprocess(PostProcessingSteps::default(), fonts);
+    Ok(tree)
+}
+
+/// the svg rasterized at `options.dpi`
+pub fn to_png(svg: &str, fonts: &fontdb::Database, options: &ExportOptions) -> Result<Vec<u8>, Error> {
+    let dpi = check_dpi(options.dpi)?;
+    let tree = parse(svg, fonts)?;
+    let scale = dpi / SVG_DPI;
+    let width = (tree.size.width() * scale).ceil() as u32;
+    let height = (tree.size.height() * scale).ceil() as u32;
+    let mut pixmap = Pixmap::new(width, height).ok_or(Error::ImageTooLarge { width, height })?;
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    Ok(pixmap.encode_png()?)
+}
+
+/// The svg as vectors in a pdf. A chart larger than a page is cut in tiles over several pages,
+/// from left to right then top to bottom, each page is labelled with its row and column
+/// so the printed pages can be put together. A chart over more than `MAX_PAGES` is an error.
+pub fn to_pdf(svg: &str, fonts: &fontdb::Database, options: &ExportOptions) -> Result<Vec<u8>, Error> {
+    let dpi = check_dpi(options.dpi)?;
+    let tree = parse(svg, fonts)?;
+    let (mut page_width, mut page_height) = options.page_size.size();
+    if options.landscape {
+        (page_width, page_height) = (page_height, page_width);
+    }
+    // the chart and the printable area of a page, in pt
+    let chart_width = tree.size.width() * 72.0 / dpi;
+    let chart_height = tree.size.height() * 72.0 / dpi;
+    let area_width = page_width - 2.0 * PAGE_MARGIN;
+    let area_height = page_height - 2.0 * PAGE_MARGIN;
+    let columns = (chart_width / area_width).ceil().max(1.0);
+    let rows = (chart_height / area_height).ceil().max(1.0);
+    // checked before the loop over the pages, a tiny dpi is a huge chart
+    if columns * rows > MAX_PAGES as f32 {
+        return Err(Error::TooManyPages { pages: columns * rows });
+    }
+    let (columns, rows) = (columns as usize, rows as usize);
+
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let chart_id = Ref::new(4);
+    let svg_options = svg2pdf::Options {
+        dpi,
+        ..Default::default()
+    };
+    // the chart is written once and drawn on each page
+    let mut next_id = svg2pdf::convert_tree_into(&tree, svg_options, &mut pdf, chart_id);
+    let mut page_ids = vec![];
+
+    let chart_name = Name(b"Chart");
+    let font_name = Name(b"F1");
+    for row in 0..rows {
+        for column in 0..columns {
+            let page_id = next_id;
+            let content_id = Ref::new(next_id.get() + 1);
+            next_id = Ref::new(next_id.get() + 2);
+            page_ids.push(page_id);
+
+            let mut content = Content::new();
+            content.save_state();
+            content.rect(PAGE_MARGIN, PAGE_MARGIN, area_width, area_height);
+            content.clip_nonzero();
+            content.end_path();
+            // the tile at `row` and `column` from the top left of the chart is moved over the printable area
+            let x = PAGE_MARGIN - column as f32 * area_width;
+            let y = PAGE_MARGIN + area_height + row as f32 * area_height - chart_height;
+            content.transform([chart_width, 0.0, 0.0, chart_height, x, y]);
+            content.x_object(chart_name);
+            content.restore_state();
+            if rows * columns > 1 {
+                let label = format!("row {} of {rows}, column {} of {columns}", row + 1, column + 1);
+                content
+                    .begin_text()
+                    .set_font(font_name, 9.0)
+                    .next_line(PAGE_MARGIN, PAGE_MARGIN / 2.0)
+                    .show(Str(label.as_bytes()))
+                    .end_text();
+            }
+            pdf.stream(content_id, &content.finish());
+
+            let mut page = pdf.page(page_id);
+            page.media_box(Rect::new(0.0, 0.0, page_width, page_height));
+            page.parent(page_tree_id);
+            page.contents(content_id);
+            let mut resources = page.resources();
+            resources.x_objects().pair(chart_name, chart_id);
+            resources.fonts().pair(font_name, font_id);
+            resources.finish();
+            page.finish();
+        }
+    }
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).count(page_ids.len() as i32).kids(page_ids);
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    Ok(pdf.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a chart of 1000 x 500 px
+    const SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="500" viewBox="0 0 1000 500">
+        <rect width="100%" height="100%" fill="white" />
+        <rect x="20" y="20" width="180" height="48" fill="#eef4fb" stroke="#4a78b5" />
+    </svg>"##;
+
+    fn fonts() -> fontdb::Database {
+        fontdb::Database::new()
+    }
+
+    fn page_count(pdf: &[u8]) -> usize {
+        let pdf = String::from_utf8_lossy(pdf);
+        pdf.matches("/Type /Page").count() - pdf.matches("/Type /Pages").count()
+    }
+
+    #[test]
+    fn png_at_the_dpi() {
+        let png = to_png(SVG, &fonts(), &ExportOptions { dpi: 48.0, ..Default::default() }).unwrap();
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (500, 250));
+    }
+
+    #[test]
+    fn large_chart_over_several_pages() {
+        // 1000 px at 96 dpi is 750 pt, wider than the 523 pt printable on a portrait A4
+        let pdf = to_pdf(SVG, &fonts(), &ExportOptions::default()).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert_eq!(page_count(&pdf), 2);
+        // twice the dpi is half the size, so it fits in a page
+        let pdf = to_pdf(SVG, &fonts(), &ExportOptions { dpi: 192.0, ..Default::default() }).unwrap();
+        assert_eq!(page_count(&pdf), 1);
+    }
+
+    #[test]
+    fn invalid_dpi() {
+        for dpi in [0.0, -96.0, f32::NAN, f32::INFINITY] {
+            let options = ExportOptions { dpi, ..Default::default() };
+            assert!(matches!(to_pdf(SVG, &fonts(), &options), Err(Error::InvalidDpi(_))));
+            assert!(matches!(to_png(SVG, &fonts(), &options), Err(Error::InvalidDpi(_))));
+        }
+        // a positive dpi so small the chart would be printed over millions of pages
+        let options = ExportOptions { dpi: 0.001, ..Default::default() };
+        assert!(matches!(to_pdf(SVG, &fonts(), &options), Err(Error::TooManyPages { .. })));
+    }
+}
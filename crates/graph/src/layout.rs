@@ -0,0 +1,220 @@
+/// The position of each node of a tidy tree, in slots:
+/// `x` is the horizontal center of the node and `depth` its level below the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TidyLayout {
+    pub x: Vec<f64>,
+    pub depth: Vec<usize>,
+}
+
+/// The arc of each node of a sunburst, in turns, and its level below the root
+#[derive(Debug, Clone, PartialEq)]
+pub struct SunburstLayout {
+    pub start: Vec<f64>,
+    pub end: Vec<f64>,
+    pub depth: Vec<usize>,
+}
+
+/// The leftmost and rightmost x at each level of a subtree.
+/// The levels are stored deepest first so the level of a new root is pushed at the end,
+/// and a whole subtree is moved by changing `shift` instead of every level.
+#[derive(Debug, Clone, Default)]
+struct Contour {
+    levels: Vec<(f64, f64)>,
+    shift: f64,
+}
+
+impl Contour {
+    fn height(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// the bounds at `level` below the root of the subtree
+    fn get(&self, level: usize) -> (f64, f64) {
+        let (left, right) = self.levels[self.levels.len() - 1 - level];
+        (left + self.shift, right + self.shift)
+    }
+
+    fn set(&mut self, level: usize, (left, right): (f64, f64)) {
+        let index = self.levels.len() - 1 - level;
+        self.levels[index] = (left - self.shift, right - self.shift);
+    }
+
+    /// Combine with the contour of a sibling, only the levels they share are visited
+    /// so laying out the whole tree stays linear.
+    fn merge(self, other: Contour) -> Contour {
+        let (mut base, other) = if self.height() >= other.height() { (self, other) } else { (other, self) };
+        for level in 0..other.height() {
+            let (base_left, base_right) = base.get(level);
+            let (left, right) = other.get(level);
+            base.set(level, (base_left.min(left), base_right.max(right)));
+        }
+        base
+    }
+}
+
+/// Lay out a tree in the manner of Reingold–Tilford.
+///
+/// The subtrees are built bottom up, each child subtree is pushed to the right of its left siblings
+/// just enough for their contours to be `separation` apart at every level, then the parent is centered
+/// over its first and last child. So a parent is always above its children, the siblings keep their order,
+/// and a subtree is drawn the same wherever it is in the tree.
+///
+/// `children` are the indexes of the children of each node, the leftmost x is 0.
+pub fn tidy(children: &[Vec<usize>], root: usize, separation: f64) -> TidyLayout {
+    let n = children.len();
+    let (order, depth) = preorder(children, root);
+
+    // the x of each node relative to its parent, the reverse of the pre-order has the children before their parent
+    let mut relative = vec![0.0; n];
+    let mut contours: Vec<Contour> = vec![Contour::default(); n];
+    for &node in order.iter().rev() {
+        let mut merged: Option<Contour> = None;
+        for &child in children[node].iter() {
+            let mut contour = std::mem::take(&mut contours[child]);
+            let shift = match &merged {
+                Some(merged) => (0..merged.height().min(contour.height()))
+                    .map(|level| merged.get(level).1 + separation - contour.get(level).0)
+                    .fold(f64::MIN, f64::max),
+                None => 0.0,
+            };
+            contour.shift += shift;
+            relative[child] = shift;
+            merged = Some(match merged {
+                Some(merged) => merged.merge(contour),
+                None => contour,
+            });
+        }
+        let mut contour = match (merged, children[node].first(), children[node].last()) {
+            (Some(mut merged), Some(first), Some(last)) => {
+                let center = (relative[*first] + relative[*last]) / 2.0;
+                for child in children[node].iter() {
+                    relative[*child] -= center;
+                }
+                merged.shift -= center;
+                merged
+            }
+            _ => Contour::default(),
+        };
+        contour.levels.push((-contour.shift, -contour.shift));
+        contours[node] = contour;
+    }
+
+    let mut x = vec![0.0; n];
+    for &node in order.iter() {
+        for child in children[node].iter() {
+            x[*child] = x[node] + relative[*child];
+        }
+    }
+    let min = order.iter().map(|node| x[*node]).fold(0.0, f64::min);
+    TidyLayout {
+        x: x.into_iter().map(|x| x - min).collect(),
+        depth,
+    }
+}
+
+/// Lay out a tree as a sunburst.
+///
+/// The root has the whole turn and each node has a part of it proportional to the size of its subtree,
+/// the children share the arc of their parent from its start in their order,
+/// the rest of the arc of the parent is the share of the parent itself.
+pub fn sunburst(children: &[Vec<usize>], root: usize) -> SunburstLayout {
+    let n = children.len();
+    let (order, depth) = preorder(children, root);
+    let sizes = subtree_sizes(children, &order);
+    let total = sizes[root] as f64;
+    let mut start = vec![0.0; n];
+    let mut end = vec![0.0; n];
+    end[root] = 1.0;
+    for &node in order.iter() {
+        let mut at = start[node];
+        for &child in children[node].iter() {
+            start[child] = at;
+            at += sizes[child] as f64 / total;
+            end[child] = at;
+        }
+    }
+    SunburstLayout { start, end, depth }
+}
+
+/// The nodes reachable from `root` in pre-order and the depth of each node,
+/// walked with a stack so a deep tree does not overflow.
+fn preorder(children: &[Vec<usize>], root: usize) -> (Vec<usize>, Vec<usize>) {
+    let mut depth = vec![0; children.len()];
+    let mut order = Vec::with_capacity(children.len());
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        order.push(node);
+        for child in children[node].iter().rev() {
+            depth[*child] = depth[node] + 1;
+            stack.push(*child);
+        }
+    }
+    (order, depth)
+}
+
+/// the number of nodes of each subtree, the node included
+fn subtree_sizes(children: &[Vec<usize>], order: &[usize]) -> Vec<usize> {
+    let mut sizes = vec![1; children.len()];
+    // the reverse of the pre-order has the children before their parent
+    for &node in order.iter().rev() {
+        sizes[node] += children[node].iter().map(|child| sizes[*child]).sum::<usize>();
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_is_centered_over_its_children() {
+        // 0 -> 1, 2, 3
+        let layout = tidy(&[vec![1, 2, 3], vec![], vec![], vec![]], 0, 1.0);
+        assert_eq!(layout.x, vec![1.0, 0.0, 1.0, 2.0]);
+        assert_eq!(layout.depth, vec![0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn subtrees_do_not_overlap() {
+        // 0 -> 1, 2; 1 -> 3, 4; 2 -> 5, 6
+        let children = vec![vec![1, 2], vec![3, 4], vec![5, 6], vec![], vec![], vec![], vec![]];
+        let layout = tidy(&children, 0, 1.0);
+        assert_eq!(layout.x, vec![1.5, 0.5, 2.5, 0.0, 1.0, 2.0, 3.0]);
+
+        // a deep left subtree pushes the right one only at the levels they share
+        // 0 -> 1, 2; 1 -> 3, 4; 3 -> 5, 6
+        let children = vec![vec![1, 2], vec![3, 4], vec![], vec![5, 6], vec![], vec![], vec![]];
+        let layout = tidy(&children, 0, 1.0);
+        for level in 0..4 {
+            let mut xs: Vec<f64> = (0..7).filter(|i| layout.depth[*i] == level).map(|i| layout.x[i]).collect();
+            xs.sort_by(f64::total_cmp);
+            assert!(xs.windows(2).all(|w| w[1] - w[0] >= 1.0), "overlap at level {level}: {xs:?}");
+        }
+        assert!(layout.x.iter().all(|x| *x >= 0.0));
+    }
+
+    #[test]
+    fn a_long_chain_does_not_overflow_the_stack() {
+        let n = 100_000;
+        let children: Vec<Vec<usize>> = (0..n).map(|i| if i + 1 < n { vec![i + 1] } else { vec![] }).collect();
+        let layout = tidy(&children, 0, 1.0);
+        assert!(layout.x.iter().all(|x| *x == 0.0));
+        assert_eq!(layout.depth[n - 1], n - 1);
+    }
+
+    #[test]
+    fn sunburst_arcs_are_proportional_to_the_subtree_size() {
+        // 0 -> 1, 2; 1 -> 3, 4, 5
+        let children = vec![vec![1, 2], vec![3, 4, 5], vec![], vec![], vec![], vec![]];
+        let layout = sunburst(&children, 0);
+        let span = |i: usize| layout.end[i] - layout.start[i];
+        assert_eq!((layout.start[0], layout.end[0]), (0.0, 1.0));
+        assert!((span(1) - 4.0 / 6.0).abs() < 1e-9);
+        assert!((span(2) - 1.0 / 6.0).abs() < 1e-9);
+        // the children are inside the arc of their parent, in order
+        assert_eq!(layout.start[3], layout.start[1]);
+        assert_eq!(layout.start[4], layout.end[3]);
+        assert!(layout.end[5] <= layout.end[1]);
+        assert_eq!(layout.depth, vec![0, 1, 1, 2, 2, 2]);
+    }
+}
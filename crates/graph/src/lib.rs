@@ -0,0 +1,11 @@
+#[cfg(feature = "export")]
+pub use error::Error;
+
+pub mod app;
+pub mod chart;
+#[cfg(feature = "export")]
+mod error;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod layout;
+pub mod tree_view;
@@ -1,10 +1,97 @@
+use std::path::PathBuf;
+use chrono_tz::Tz;
+use clap::Parser;
 use sauron::*;
+use customer::{CustomerTree, LoadOptions};
 
-use app::App;
+use graph::app::App;
+use graph::chart::{Chart, ChartOptions, Layout};
+use graph::export::{self, ExportOptions, PageSize, SVG_DPI};
 
-mod app;
+/// Draw the org chart of a customer and everyone placed below it as an svg, a png or a pdf
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// the customer at the top of the chart
+    eq_id: u64,
+    /// a csv or gzipped csv export to read instead of the customer data embedded in the binary
+    #[arg(long, short)]
+    input: Option<PathBuf>,
+    /// the timezone of the dates in the export which have no offset, eg: `Europe/Oslo`
+    #[arg(long, default_value_t = Tz::UTC)]
+    timezone: Tz,
+    /// do not go deeper than this level, the direct children are at depth 1
+    #[arg(long)]
+    depth: Option<usize>,
+    /// stop after drawing this many customers below the top one
+    #[arg(long)]
+    max_nodes: Option<usize>,
+    /// a top down tree, or a radial tree or a sunburst for a large downline
+    #[arg(long, short, value_enum, default_value_t)]
+    layout: Layout,
+    /// the file to write, an .svg, a .png or a .pdf
+    #[arg(long, short, default_value = "chart.svg")]
+    output: PathBuf,
+    /// the pixels per inch of the png, and how many px of the chart are printed in an inch of the pdf
+    #[arg(long, default_value_t = SVG_DPI, value_parser = dpi)]
+    dpi: f32,
+    /// the paper of the pdf, a chart larger than a page is printed over several pages
+    #[arg(long, value_enum, default_value_t)]
+    page_size: PageSize,
+    /// the pdf pages are wider than high
+    #[arg(long)]
+    landscape: bool,
+}
+
+/// a positive number
+fn dpi(value: &str) -> Result<f32, String> {
+    let dpi: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    export::check_dpi(dpi).map_err(|e| e.to_string())
+}
 
-fn main() {
-    let app = App{};
-    println!("{}", app.view().render_to_string());
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let load_options = LoadOptions {
+        timezone: cli.timezone,
+        ..Default::default()
+    };
+    let tree = match cli.input.as_deref() {
+        Some(path) => CustomerTree::from_path(path, &load_options)?,
+        None => customer::customer_tree_with(&load_options)?,
+    };
+    let options = ChartOptions {
+        max_depth: cli.depth,
+        max_nodes: cli.max_nodes,
+        layout: cli.layout,
+    };
+    let Some(chart) = Chart::new(&tree, cli.eq_id, &options) else {
+        anyhow::bail!("there is no customer with eq_id {}", cli.eq_id);
+    };
+    let count = chart.nodes.len();
+    let app = App::new(chart);
+    let svg = app.view().render_to_string();
+    let export_options = ExportOptions {
+        dpi: cli.dpi,
+        page_size: cli.page_size,
+        landscape: cli.landscape,
+    };
+    let extension = cli.output.extension().and_then(|extension| extension.to_str()).map(str::to_lowercase);
+    let bytes = match extension.as_deref() {
+        Some("svg") => svg.into_bytes(),
+        Some(format @ ("png" | "pdf")) => {
+            let fonts = export::system_fonts();
+            if fonts.is_empty() {
+                eprintln!("warning: no system font found, the text of the chart is left out");
+            }
+            if format == "png" {
+                export::to_png(&svg, &fonts, &export_options)?
+            } else {
+                export::to_pdf(&svg, &fonts, &export_options)?
+            }
+        }
+        _ => anyhow::bail!("the output has to be an .svg, a .png or a .pdf file"),
+    };
+    std::fs::write(&cli.output, bytes)?;
+    println!("{count} customer(s) written to {}", cli.output.display());
+    Ok(())
 }
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+use sauron::*;
+use customer::CustomerCount;
+use crate::app::{customer_box, edge, PARENT_COLOR};
+use crate::chart::{Chart, ChartNode, BOX_HEIGHT, BOX_WIDTH};
+
+/// the radius of the button which expands or collapses a customer
+const TOGGLE_RADIUS: f64 = 8.0;
+
+/// A customer shown in the tree view
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeItem {
+    pub eq_id: u64,
+    pub full_name: String,
+    pub child_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Msg {
+    /// expand or collapse the children of a customer
+    Toggle(u64),
+    Select(u64),
+    ReceiveRoots(Vec<TreeItem>),
+    ReceiveChildren(u64, Vec<TreeItem>),
+}
+
+/// What the app has to do after an update, the tree view does not query the data itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// the children of the customer are needed, answer with `Msg::ReceiveChildren`
+    LoadChildren(u64),
+    /// the customer was selected
+    Selected(u64),
+}
+
+/// The customer tree drawn as an org chart, where a customer is expanded or collapsed on click.
+/// The children of a customer are only loaded the first time it is expanded.
+#[derive(Debug, Default)]
+pub struct TreeView {
+    roots: Vec<u64>,
+    items: HashMap<u64, TreeItem>,
+    /// the children of the customers which are loaded
+    children: HashMap<u64, Vec<u64>>,
+    expanded: HashSet<u64>,
+    selected: Option<u64>,
+}
+
+impl From<CustomerCount> for TreeItem {
+    fn from(count: CustomerCount) -> Self {
+        TreeItem {
+            eq_id: count.eq_id,
+            full_name: count.full_name,
+            child_count: count.children as usize,
+        }
+    }
+}
+
+impl TreeView {
+    pub fn update(&mut self, msg: Msg) -> Option<Request> {
+        match msg {
+            Msg::Toggle(eq_id) => {
+                if self.expanded.remove(&eq_id) {
+                    return None;
+                }
+                self.expanded.insert(eq_id);
+                if self.children.contains_key(&eq_id) {
+                    None
+                } else {
+                    Some(Request::LoadChildren(eq_id))
+                }
+            }
+            Msg::Select(eq_id) => {
+                self.selected = Some(eq_id);
+                Some(Request::Selected(eq_id))
+            }
+            Msg::ReceiveRoots(items) => {
+                self.roots = self.insert(items);
+                None
+            }
+            Msg::ReceiveChildren(eq_id, items) => {
+                let children = self.insert(items);
+                self.children.insert(eq_id, children);
+                None
+            }
+        }
+    }
+
+    /// keep the items and return their eq_id in order
+    fn insert(&mut self, items: Vec<TreeItem>) -> Vec<u64> {
+        items
+            .into_iter()
+            .map(|item| {
+                let eq_id = item.eq_id;
+                self.items.insert(eq_id, item);
+                eq_id
+            })
+            .collect()
+    }
+
+    /// The customers which are shown, the parents before their children:
+    /// the roots and the loaded children of the expanded customers.
+    /// A customer is shown once, a record which is its own parent or a cycle is not followed again.
+    fn visible(&self) -> Vec<ChartNode> {
+        let mut nodes = vec![];
+        let mut visited = HashSet::new();
+        let mut stack: Vec<(u64, Option<usize>)> = self.roots.iter().rev().map(|eq_id| (*eq_id, None)).collect();
+        while let Some((eq_id, parent)) = stack.pop() {
+            let Some(item) = self.items.get(&eq_id) else {
+                continue;
+            };
+            if !visited.insert(eq_id) {
+                continue;
+            }
+            let index = nodes.len();
+            nodes.push(ChartNode {
+                eq_id,
+                full_name: item.full_name.clone(),
+                child_count: item.child_count,
+                parent,
+                sponsor_eq_id: None,
+                sponsor: None,
+                depth: 0,
+                x: 0.0,
+                y: 0.0,
+                angle: (0.0, 0.0),
+            });
+            if let (true, Some(children)) = (self.expanded.contains(&eq_id), self.children.get(&eq_id)) {
+                stack.extend(children.iter().rev().map(|child| (*child, Some(index))));
+            }
+        }
+        nodes
+    }
+
+    pub fn view(&self) -> Node<Msg> {
+        let chart = Chart::tree(self.visible());
+        node!{
+            <svg xmlns="http://www.w3.org/2000/svg"
+                width={chart.width.to_string()}
+                height={chart.height.to_string()}
+                viewBox={format!("0 0 {} {}", chart.width, chart.height)}
+                font-family="sans-serif">
+              <g fill="none" stroke={PARENT_COLOR} stroke-width="1.5">
+                {for (parent, child) in chart.edges() { edge(parent, child) }}
+              </g>
+              {for node in chart.nodes.iter() { self.view_node(node) }}
+            </svg>
+        }
+    }
+
+    /// the box selects the customer, the button below it expands or collapses its children
+    fn view_node(&self, node: &ChartNode) -> Node<Msg> {
+        let eq_id = node.eq_id;
+        let selected = if self.selected == Some(eq_id) {
+            node!{
+                <rect x={(node.x - BOX_WIDTH / 2.0 - 3.0).to_string()} y={(node.y - BOX_HEIGHT / 2.0 - 3.0).to_string()}
+                    width={(BOX_WIDTH + 6.0).to_string()} height={(BOX_HEIGHT + 6.0).to_string()} rx="8"
+                    fill="none" stroke="#d9822b" stroke-width="3" />
+            }
+        } else {
+            node!{<g />}
+        };
+        let toggle = if node.child_count == 0 {
+            node!{<g />}
+        } else {
+            let (x, y) = node.bottom();
+            let sign = match (self.expanded.contains(&eq_id), self.children.contains_key(&eq_id)) {
+                (false, _) => "+",
+                (true, true) => "−",
+                // the children are being loaded
+                (true, false) => "…",
+            };
+            node!{
+                <g transform={format!("translate({x},{y})")} cursor="pointer" on_click={move |_| Msg::Toggle(eq_id)}>
+                  <circle r={TOGGLE_RADIUS.to_string()} fill="white" stroke="#4a78b5" stroke-width="1.5" />
+                  <text dy="0.35em" font-size="12" text-anchor="middle">{text(sign)}</text>
+                </g>
+            }
+        };
+        node!{
+            <g>
+              <g cursor="pointer" on_click={move |_| Msg::Select(eq_id)}>
+                {customer_box(node)}
+              </g>
+              {selected}
+              {toggle}
+            </g>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(eq_id: u64, child_count: usize) -> TreeItem {
+        TreeItem {
+            eq_id,
+            full_name: format!("Customer {eq_id}"),
+            child_count,
+        }
+    }
+
+    #[test]
+    fn children_are_loaded_once() {
+        let mut view = TreeView::default();
+        view.update(Msg::ReceiveRoots(vec![item(1, 2), item(9, 0)]));
+        assert_eq!(view.update(Msg::Toggle(1)), Some(Request::LoadChildren(1)));
+        view.update(Msg::ReceiveChildren(1, vec![item(2, 0), item(3, 0)]));
+        let eq_ids: Vec<u64> = view.visible().iter().map(|node| node.eq_id).collect();
+        assert_eq!(eq_ids, vec![1, 2, 3, 9]);
+
+        // collapsed, then expanded again without loading
+        assert_eq!(view.update(Msg::Toggle(1)), None);
+        assert_eq!(view.visible().len(), 2);
+        assert_eq!(view.update(Msg::Toggle(1)), None);
+        assert_eq!(view.visible().len(), 4);
+        assert_eq!(view.update(Msg::Select(3)), Some(Request::Selected(3)));
+    }
+
+    #[test]
+    fn cycle_is_shown_once() {
+        let mut view = TreeView::default();
+        view.update(Msg::ReceiveRoots(vec![item(1, 1)]));
+        view.update(Msg::Toggle(1));
+        // 1 is its own child, and 2 is the parent of its parent
+        view.update(Msg::ReceiveChildren(1, vec![item(1, 1), item(2, 1)]));
+        view.update(Msg::Toggle(2));
+        view.update(Msg::ReceiveChildren(2, vec![item(1, 1)]));
+        let eq_ids: Vec<u64> = view.visible().iter().map(|node| node.eq_id).collect();
+        assert_eq!(eq_ids, vec![1, 2]);
+    }
+}
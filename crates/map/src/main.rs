@@ -6,9 +6,7 @@ use arrow::datatypes::DataType;
 use arrow::datatypes::Field;
 use arrow::datatypes::Fields;
 use arrow::datatypes::Schema;
-use csv::ReaderBuilder;
-use csv::StringRecord;
-use csv::Terminator;
+use address::Address;
 use datafusion::common::cast::as_float64_array;
 use datafusion::common::cast::as_string_array;
 use datafusion::logical_expr_common::signature::Volatility;
@@ -45,23 +43,7 @@ async fn main() -> anyhow::Result<()> {
         let args = ColumnarValue::values_to_arrays(args)?;
         let base = as_string_array(&args[0]).expect("cast failed");
         fn get_city(addr: &str) -> Option<String> {
-            let mut rdr = ReaderBuilder::new()
-                .has_headers(false)
-                .terminator(Terminator::Any(0))
-                .from_reader(addr.as_bytes());
-
-            let records: Vec<StringRecord> = rdr
-                .records()
-                .map(|r| match r {
-                    Ok(r) => r,
-                    Err(e) => {
-                        dbg!(&addr);
-                        panic!("error here: {e}")
-                    }
-                })
-                .collect();
-            assert_eq!(records.len(), 1);
-            records[0].iter().rev().nth(2).map(|s| s.trim().to_string())
+            Address::parse(addr).city
         }
         let array = base
             .iter()
@@ -87,23 +69,7 @@ async fn main() -> anyhow::Result<()> {
         let args = ColumnarValue::values_to_arrays(args)?;
         let base = as_string_array(&args[0]).expect("cast failed");
         fn get_country(addr: &str) -> Option<String> {
-            let mut rdr = ReaderBuilder::new()
-                .has_headers(false)
-                .terminator(Terminator::Any(0))
-                .from_reader(addr.as_bytes());
-
-            let records: Vec<StringRecord> = rdr
-                .records()
-                .map(|r| match r {
-                    Ok(r) => r,
-                    Err(e) => {
-                        dbg!(&addr);
-                        panic!("error here: {e}")
-                    }
-                })
-                .collect();
-            assert_eq!(records.len(), 1);
-            records[0].iter().rev().next().map(|s| s.trim().to_string())
+            Address::parse(addr).country
         }
         let array = base
             .iter()
@@ -146,6 +112,9 @@ async fn main() -> anyhow::Result<()> {
 
 #[test]
 fn test1() {
+    use csv::ReaderBuilder;
+    use csv::StringRecord;
+
     let addr = "Raadhuisstraat (2401231509), Amsterdam, 3036, NO";
     let mut rdr = ReaderBuilder::new()
         .has_headers(false)
@@ -0,0 +1,40 @@
+//! Write the masked customer table embedded in the viewer, so the clear export
+//! and the masking key never end up in the wasm served to the browser.
+use std::collections::hash_map::RandomState;
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::path::PathBuf;
+use customer::{CustomerRecords, LoadOptions, Masking, MASKING_KEY_VAR};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+const EXPORT: &str = "../../data/customer_export.gz";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed={EXPORT}");
+    println!("cargo:rerun-if-env-changed={MASKING_KEY_VAR}");
+
+    // without a key the tokens are still masked, they just differ from one build to the next
+    let masking = Masking::from_env().unwrap_or_else(|| {
+        println!("cargo:warning={MASKING_KEY_VAR} is not set, the customer table is masked with a random key");
+        let key = RandomState::new().build_hasher().finish();
+        Masking::new(format!("{key:016x}"))
+    });
+    let options = LoadOptions {
+        masking: Some(masking),
+        ..Default::default()
+    };
+    let mut records = CustomerRecords::new(File::open(EXPORT)?, &options)?;
+    let customers = records.by_ref().collect::<Result<Vec<_>, _>>()?;
+    for warning in records.warnings() {
+        println!("cargo:warning={warning}");
+    }
+    let csv = customer::customer_table_csv(&customers, &options)?;
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    let mut encoder = GzEncoder::new(File::create(out_dir.join("customer_masked.csv.gz"))?, Compression::default());
+    encoder.write_all(&csv)?;
+    encoder.finish()?;
+    Ok(())
+}
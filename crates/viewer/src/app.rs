@@ -1,9 +1,26 @@
-use customer;
+use std::cell::OnceCell;
+use std::rc::Rc;
+use customer::{CustomerCount, Query};
 use data_viewer::views::{data_view, DataView};
 use gauntlet::Context;
 use gauntlet::DataPane;
+use graph::tree_view::{self, Request, TreeItem, TreeView};
 use sauron::*;
 
+/// The masked customer table written by the build script, so the browser only gets to see
+/// the tokens of the personal data, the clear export and the masking key are not in the wasm
+static CUSTOMER_TABLE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/customer_masked.csv.gz"));
+/// the most customers loaded at the top of the tree view, and below an expanded customer
+const ROOT_LIMIT: u64 = 100;
+const CHILDREN_LIMIT: u64 = 1000;
+
+thread_local! {
+    /// the context with the customer table, loaded by the first query
+    static CONTEXT: OnceCell<Rc<Context>> = const { OnceCell::new() };
+}
+
+// the messages of a child view are named after it, eg: `DataViewMsg`
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum Msg {
     ReceiveDataPane(DataPane),
@@ -13,7 +30,8 @@ pub enum Msg {
     StartResize(Grip, i32, i32),
     SqlChanged(String),
     ExecuteSql,
-    QueryError(gauntlet::Error),
+    QueryError(customer::Error),
+    TreeViewMsg(tree_view::Msg),
 }
 
 /// provides a resizable wrapper for the DataView
@@ -22,7 +40,8 @@ pub struct App {
     data_view: Option<DataView>,
     active_resize: Option<Grip>,
     sql: String,
-    query_error: Option<gauntlet::Error>,
+    query_error: Option<customer::Error>,
+    tree_view: TreeView,
     width: i32,
     height: i32,
     start_x: i32,
@@ -43,6 +62,7 @@ impl App {
             active_resize: None,
             sql: "SELECT * FROM customer LIMIT 10".to_string(),
             query_error: None,
+            tree_view: TreeView::default(),
             width: 400,
             height: 500,
             start_x: 0,
@@ -53,10 +73,48 @@ impl App {
     fn execute_sql(&mut self) -> Cmd<Msg>{
         let sql = self.sql.clone();
         Cmd::new(async move{
-            let ctx = Context::new();
-            let data_pane = customer::customer_data().await.unwrap();
-            ctx.register_table("customer", data_pane).unwrap();
+            let ctx = context();
             match ctx.sql(&sql).await{
+                Ok(records) => Msg::ReceiveDataPane(records),
+                Err(e) => Msg::QueryError(e.into()),
+            }
+        })
+    }
+
+    /// the customers at the top of the tree view
+    fn load_roots(&self) -> Cmd<Msg> {
+        Cmd::new(async move {
+            let ctx = context();
+            match Query::roots(ROOT_LIMIT).fetch::<CustomerCount>(&ctx).await {
+                Ok(roots) => {
+                    let roots = roots.into_iter().map(TreeItem::from).collect();
+                    Msg::TreeViewMsg(tree_view::Msg::ReceiveRoots(roots))
+                }
+                Err(e) => Msg::QueryError(e),
+            }
+        })
+    }
+
+    /// the children of a customer expanded for the first time in the tree view
+    fn load_children(&self, eq_id: u64) -> Cmd<Msg> {
+        Cmd::new(async move {
+            let ctx = context();
+            match Query::children(eq_id, CHILDREN_LIMIT).fetch::<CustomerCount>(&ctx).await {
+                Ok(children) => {
+                    let children = children.into_iter().map(TreeItem::from).collect();
+                    Msg::TreeViewMsg(tree_view::Msg::ReceiveChildren(eq_id, children))
+                }
+                Err(e) => Msg::QueryError(e),
+            }
+        })
+    }
+
+    /// the full row of the customer selected in the tree view, shown in the data view
+    fn show_customer(&self, eq_id: u64) -> Cmd<Msg> {
+        Cmd::new(async move {
+            let ctx = context();
+            let query = Query::new("SELECT * FROM customer WHERE eq_id = ?").arg(eq_id);
+            match query.fetch_pane(&ctx).await {
                 Ok(records) => Msg::ReceiveDataPane(records),
                 Err(e) => Msg::QueryError(e),
             }
@@ -64,6 +122,19 @@ impl App {
     }
 }
 
+/// The context with the customer table, which is loaded once and shared by the queries.
+/// The loading does not await, so the queries started together by `init` can not load it twice.
+fn context() -> Rc<Context> {
+    CONTEXT.with(|ctx| {
+        Rc::clone(ctx.get_or_init(|| {
+            let ctx = Context::new();
+            let data_source = customer::load_table(CUSTOMER_TABLE).unwrap();
+            ctx.register_table("customer", data_source).unwrap();
+            Rc::new(ctx)
+        }))
+    })
+}
+
 impl Application for App {
     type MSG = Msg;
 
@@ -79,6 +150,7 @@ impl Application for App {
             Window::on_mouseup(|event| Msg::EndResize(event.client_x(), event.client_y())),
             Window::on_mousemove(|event| Msg::MouseMove(event.client_x(), event.client_y())),
             self.execute_sql(),
+            self.load_roots(),
         ])
     }
 
@@ -168,6 +240,11 @@ impl Application for App {
                 self.query_error = Some(e);
                 Cmd::none()
             }
+            Msg::TreeViewMsg(tree_view_msg) => match self.tree_view.update(tree_view_msg) {
+                Some(Request::LoadChildren(eq_id)) => self.load_children(eq_id),
+                Some(Request::Selected(eq_id)) => self.show_customer(eq_id),
+                None => Cmd::none(),
+            },
         }
     }
 
@@ -194,6 +271,10 @@ impl Application for App {
                     },
                     ],
                 ),
+                div(
+                    [class("tree_view")],
+                    [self.tree_view.view().map_msg(Msg::TreeViewMsg)],
+                ),
                 div(
                     [class("resize_wrapper grid")],
                     [
@@ -247,6 +328,11 @@ impl Application for App {
         vec![jss! {
             "body": {
                 font_family: "Fira Sans, Courier New, Courier, Lucida Sans Typewriter, Lucida Typewriter, monospace",
+            },
+            ".tree_view": {
+                overflow: "auto",
+                max_height: "400px",
+                border: "1px solid #dddddd",
             }
         }]
     }
@@ -1 +1,2 @@
+# the customer table embedded in the viewer is masked with CUSTOMER_MASKING_KEY, a random key when it is not set
 wasm-pack build --release --target=web && basic-http-server -a 0.0.0.0:5151
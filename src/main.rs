@@ -1,71 +1,289 @@
-use gauntlet::Context;
-use gauntlet::DataPane;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand};
+use serde_json::json;
+use customer::{CustomerCount, CustomerData, CustomerTree, DiffRow, DownlineOptions, LoadOptions, Query, Relation, Table};
+use crate::output::{Format, Rows};
+use crate::session::Session;
 
-async fn get_children(ctx: &Context, eq_id: u64) -> anyhow::Result<DataPane>{
-    let sql = format!("SELECT eq_id, full_name, parent_eq_id,
-                (SELECT COUNT(*) as children 
-                    FROM customer t1 
-                    WHERE t1.parent_eq_id=customer.eq_id)
-        FROM customer 
-        WHERE customer.parent_eq_id = {eq_id} 
-        ORDER BY children DESC
-        LIMIT 5");
-    let d1 = ctx.sql(&sql).await?;
-    Ok(d1)
-}
+mod output;
+mod repl;
+mod session;
 
-async fn get_sponsored(ctx: &Context, eq_id: u64) -> anyhow::Result<DataPane>{
-    let sql = format!("SELECT eq_id, full_name, parent_eq_id,
-                (SELECT COUNT(*) as children 
-                    FROM customer t1 
-                    WHERE t1.parent_eq_id=customer.eq_id)
-        FROM customer 
-        WHERE customer.sponsor_eq_id = {eq_id} 
-        ORDER BY children DESC
-        LIMIT 5");
-    let d1 = ctx.sql(&sql).await?;
-    Ok(d1)
+/// Answer questions about the customer tree
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// a csv or gzipped csv export to read instead of the customer data embedded in the binary
+    #[arg(long, short, global = true)]
+    input: Option<PathBuf>,
+    /// the timezone of the dates in the export which have no offset, eg: `Europe/Oslo`
+    #[arg(long, global = true, default_value_t = Tz::UTC)]
+    timezone: Tz,
+    /// how the result is written
+    #[arg(long, short, global = true, value_enum, default_value_t)]
+    format: Format,
+    /// write the result to this file instead of stdout
+    #[arg(long, short, global = true)]
+    output: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
 }
 
-async fn get_sponsored_count(ctx:&Context, eq_id: u64) -> anyhow::Result<i64>{
-    let sql = format!("SELECT count(*) AS count
-        FROM customer 
-        WHERE customer.sponsor_eq_id = {eq_id}");
-    let d1 = ctx.sql(&sql).await?;
-    let count: i64 = Into::into(&d1.row_values[0][0]);
-    Ok(count)
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// the size and shape of the tree
+    Stats,
+    /// the customers with the most children
+    Top {
+        #[arg(long, default_value_t = 10)]
+        limit: u64,
+    },
+    /// the children of a customer, the ones with the most children first
+    Children {
+        eq_id: u64,
+        #[arg(long, default_value_t = 5)]
+        limit: u64,
+    },
+    /// the customers sponsored by a customer, the ones with the most children first
+    Sponsored {
+        eq_id: u64,
+        #[arg(long, default_value_t = 5)]
+        limit: u64,
+    },
+    /// all the customers below a customer
+    Downline {
+        eq_id: u64,
+        /// do not go deeper than this level, the direct children are at depth 1
+        #[arg(long)]
+        depth: Option<usize>,
+        /// follow the `parent` or the `sponsor` edges
+        #[arg(long, default_value_t)]
+        relation: Relation,
+    },
+    /// the customers above a customer, the nearest first
+    Upline {
+        eq_id: u64,
+        /// follow the `parent` or the `sponsor` edges
+        #[arg(long, default_value_t)]
+        relation: Relation,
+    },
+    /// run a sql query on the `customer` and `duplicate` tables
+    Query { sql: String },
+    /// an interactive sql prompt on the `customer` and `duplicate` tables
+    Repl,
+    /// check the tree for missing, repeated and circular references
+    Validate,
+    /// the customers which are likely the same person
+    Duplicates {
+        /// the minimum score, from 0.0 to 1.0, for a pair to be considered a duplicate
+        #[arg(long)]
+        threshold: Option<f64>,
+    },
+    /// the differences between an older and a newer export
+    Diff { before: PathBuf, after: PathBuf },
 }
 
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let data_source = customer::customer_data().await?;
+    let cli = Cli::parse();
+    let input = cli.input.as_deref();
+    let load_options = LoadOptions {
+        timezone: cli.timezone,
+        ..Default::default()
+    };
+    let rows = match cli.command {
+        Command::Stats => stats(&tree(input, &load_options)?),
+        Command::Top { limit } => {
+            let session = Session::new(data(input, &load_options).await?)?;
+            customer_counts(Query::top_by_children(limit).fetch(session.context()).await?)
+        }
+        Command::Children { eq_id, limit } => {
+            let session = Session::new(data(input, &load_options).await?)?;
+            customer_counts(Query::children(eq_id, limit).fetch(session.context()).await?)
+        }
+        Command::Sponsored { eq_id, limit } => {
+            let session = Session::new(data(input, &load_options).await?)?;
+            customer_counts(Query::sponsored(eq_id, limit).fetch(session.context()).await?)
+        }
+        Command::Downline { eq_id, depth, relation } => {
+            let tree = tree(input, &load_options)?;
+            found(&tree, eq_id)?;
+            let options = DownlineOptions {
+                relation,
+                max_depth: depth,
+                ..Default::default()
+            };
+            let mut rows = Rows::new(&["eq_id", "full_name", "depth", "path"]);
+            for entry in tree.downline(eq_id, &options) {
+                let path: Vec<String> = entry.path.iter().map(u64::to_string).collect();
+                rows.push(vec![
+                    json!(entry.eq_id),
+                    json!(full_name(&tree, entry.eq_id)),
+                    json!(entry.depth),
+                    json!(path.join(" > ")),
+                ]);
+            }
+            rows
+        }
+        Command::Upline { eq_id, relation } => {
+            let tree = tree(input, &load_options)?;
+            found(&tree, eq_id)?;
+            let mut rows = Rows::new(&["level", "eq_id", "full_name"]);
+            for (level, above) in tree.upline_by(eq_id, relation).into_iter().enumerate() {
+                rows.push(vec![json!(level + 1), json!(above), json!(full_name(&tree, above))]);
+            }
+            rows
+        }
+        Command::Query { sql } => {
+            let mut session = Session::new(data(input, &load_options).await?)?;
+            session.query(&sql).await?
+        }
+        Command::Repl => {
+            let mut session = Session::new(data(input, &load_options).await?)?;
+            return repl::run(&mut session, cli.format).await;
+        }
+        Command::Validate => {
+            let data = data(input, &load_options).await?;
+            let report = data.validate();
+            let mut rows = Rows::new(&["severity", "issue"]);
+            for issue in report.issues.iter() {
+                let severity = if issue.is_error() { "error" } else { "warning" };
+                rows.push(vec![json!(severity), json!(issue.to_string())]);
+            }
+            // the values which could not be parsed and were loaded as null
+            for warning in data.warnings.iter() {
+                rows.push(vec![json!("warning"), json!(warning.to_string())]);
+            }
+            write(&rows, cli.format, cli.output.as_deref())?;
+            if report.has_errors() {
+                anyhow::bail!("the customer data has {} error(s)", report.errors().count());
+            }
+            return Ok(());
+        }
+        Command::Duplicates { threshold } => {
+            let mut options = customer::DedupeOptions::default();
+            if let Some(threshold) = threshold {
+                options.threshold = threshold;
+            }
+            let clusters = data(input, &load_options).await?.duplicates(&options);
+            let mut rows = Rows::new(&["cluster", "eq_id", "duplicate_eq_id", "score", "reasons"]);
+            for cluster in clusters.iter() {
+                for pair in cluster.pairs.iter() {
+                    let reasons: Vec<String> = pair.reasons.iter().map(ToString::to_string).collect();
+                    rows.push(vec![
+                        json!(cluster.eq_ids.first()),
+                        json!(pair.eq_id),
+                        json!(pair.duplicate_eq_id),
+                        json!((pair.score * 100.0).round() / 100.0),
+                        json!(reasons.join("; ")),
+                    ]);
+                }
+            }
+            rows
+        }
+        Command::Diff { before, after } => {
+            let diff = customer::ExportDiff::from_paths(before, after, &load_options)?;
+            if cli.format == Format::Json {
+                // the structured diff, with the added, removed, changes and moves apart
+                let mut out = output(cli.output.as_deref())?;
+                writeln!(out, "{}", diff.to_json()?)?;
+                out.flush()?;
+                return Ok(());
+            }
+            let columns: Vec<&str> = DiffRow::COLUMNS.iter().map(|column| column.name).collect();
+            let mut rows = Rows::new(&columns);
+            for row in diff.rows() {
+                rows.push(vec![
+                    json!(row.change),
+                    json!(row.eq_id),
+                    json!(row.field),
+                    json!(row.before),
+                    json!(row.after),
+                ]);
+            }
+            rows
+        }
+    };
+    write(&rows, cli.format, cli.output.as_deref())
+}
 
-    let ctx = Context::new();
+/// stdout, or the file at `path`
+fn output(path: Option<&Path>) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
 
-    ctx.register_table("customer", data_source)?;
-    let count = ctx.sql("SELECT COUNT(*) FROM customer").await?;
-    count.show()?;
-    // top-level customer with no recruiter
-    let data = ctx.sql("SELECT eq_id, full_name, 
-                (SELECT COUNT(*) as children 
-                    FROM customer t1 
-                    WHERE t1.parent_eq_id=customer.eq_id)
-                FROM customer 
-                ORDER BY children DESC 
-                LIMIT 10").await?;
+fn write(rows: &Rows, format: Format, path: Option<&Path>) -> anyhow::Result<()> {
+    let mut out = output(path)?;
+    match rows.write(format, &mut out).and_then(|_| out.flush()) {
+        // the reader stopped early, eg: piped into `head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// the customer data of the input file, or the one embedded in the binary
+async fn data(input: Option<&Path>, options: &LoadOptions) -> anyhow::Result<CustomerData> {
+    Ok(match input {
+        Some(path) => customer::load_path(path, options).await?,
+        None => customer::customer_data_with(options).await?,
+    })
+}
 
-    data.show()?;
+/// the tree of the input file, or of the embedded customer data
+fn tree(input: Option<&Path>, options: &LoadOptions) -> anyhow::Result<CustomerTree> {
+    Ok(match input {
+        Some(path) => CustomerTree::from_path(path, options)?,
+        None => customer::customer_tree_with(options)?,
+    })
+}
 
-    for data in data.row_values{
-        let eq_id = Into::into(&data[0]);
-        let full_name: String = Into::into(&data[1]);
-        let children: u64 = Into::into(&data[2]);
-        let sponsored: i64 = get_sponsored_count(&ctx, eq_id).await?;
-        println!("{eq_id}, {full_name}, children: {children}, sponsored: {sponsored}");
-        let children = get_children(&ctx, eq_id).await?;
-        println!("children (top 5):");
-        children.show()?;
+fn found(tree: &CustomerTree, eq_id: u64) -> anyhow::Result<()> {
+    if !tree.contains(eq_id) {
+        anyhow::bail!("there is no customer with eq_id {eq_id}");
     }
     Ok(())
 }
+
+fn full_name(tree: &CustomerTree, eq_id: u64) -> Option<&str> {
+    tree.get(eq_id).map(|node| node.full_name())
+}
+
+fn customer_counts(counts: Vec<CustomerCount>) -> Rows {
+    let mut rows = Rows::new(&["eq_id", "full_name", "children"]);
+    for CustomerCount { eq_id, full_name, children } in counts {
+        rows.push(vec![json!(eq_id), json!(full_name), json!(children)]);
+    }
+    rows
+}
+
+/// 1 row per added, removed, changed value and move
+fn stats(tree: &CustomerTree) -> Rows {
+    let roots: Vec<u64> = tree.roots().map(|node| node.eq_id()).collect();
+    let max_depth = roots
+        .iter()
+        .flat_map(|root| tree.downline(*root, &DownlineOptions::default()))
+        .map(|entry| entry.depth)
+        .max()
+        .unwrap_or(0);
+    let most_children = tree.iter().max_by_key(|node| node.children().len());
+    let stats = [
+        ("customers", json!(tree.len())),
+        ("roots", json!(roots.len())),
+        ("leaves", json!(tree.iter().filter(|node| node.children().is_empty()).count())),
+        ("sponsors", json!(tree.iter().filter(|node| !node.sponsored().is_empty()).count())),
+        ("max_depth", json!(max_depth)),
+        ("most_children", json!(most_children.map(|node| node.eq_id()))),
+        ("most_children_count", json!(most_children.map(|node| node.children().len()))),
+    ];
+    let mut rows = Rows::new(&["stat", "value"]);
+    for (stat, value) in stats {
+        rows.push(vec![json!(stat), value]);
+    }
+    rows
+}
@@ -0,0 +1,256 @@
+use std::io::{self, Write};
+use clap::ValueEnum;
+use gauntlet::{DataPane, Value};
+use serde_json::{Map, Value as Json};
+
+/// How the result of a command is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    /// a plain text table with aligned columns
+    #[default]
+    Table,
+    /// an array of objects keyed by the column names
+    Json,
+    /// comma separated values with a header, null is empty
+    Csv,
+    /// 1 json object per line
+    Ndjson,
+    /// a markdown table
+    Markdown,
+}
+
+/// The result of a command, as named columns and rows of json values
+/// so the tree results and the query results are written the same way
+#[derive(Debug, Default)]
+pub struct Rows {
+    /// empty when the names are not known, the rows are then written without a header
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Json>>,
+}
+
+impl Rows {
+    pub fn new(columns: &[&str]) -> Self {
+        Rows {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Json>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    /// the result of a sql query, gauntlet does not give the names of its columns
+    pub fn from_pane(pane: &DataPane) -> Self {
+        Rows {
+            columns: vec![],
+            rows: pane
+                .row_values
+                .iter()
+                .map(|row| row.iter().map(to_json).collect())
+                .collect(),
+        }
+    }
+
+    pub fn write(&self, format: Format, out: &mut impl Write) -> io::Result<()> {
+        match format {
+            Format::Table => self.write_table(out),
+            Format::Json => {
+                let records: Vec<Json> = self.records().collect();
+                serde_json::to_writer_pretty(&mut *out, &records)?;
+                writeln!(out)
+            }
+            Format::Csv => self.write_csv(out),
+            Format::Ndjson => {
+                for record in self.records() {
+                    serde_json::to_writer(&mut *out, &record)?;
+                    writeln!(out)?;
+                }
+                Ok(())
+            }
+            Format::Markdown => self.write_markdown(out),
+        }
+    }
+
+    fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(out);
+        if !self.columns.is_empty() {
+            writer.write_record(&self.columns)?;
+        }
+        for row in self.rows.iter() {
+            writer.write_record(row.iter().map(to_text))?;
+        }
+        writer.flush()
+    }
+
+    fn write_markdown(&self, out: &mut impl Write) -> io::Result<()> {
+        // a pipe would end the cell and a line break the row
+        let cell = |text: &str| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+        // a markdown table has a header, left blank when the names are not known
+        let header: Vec<String> = match self.columns.is_empty() {
+            true => vec![String::new(); self.width()],
+            false => self.columns.iter().map(|column| cell(column)).collect(),
+        };
+        writeln!(out, "| {} |", header.join(" | "))?;
+        writeln!(out, "|{}", " --- |".repeat(header.len()))?;
+        for row in self.rows.iter() {
+            let cells: Vec<String> = row.iter().map(|value| cell(&to_text(value))).collect();
+            writeln!(out, "| {} |", cells.join(" | "))?;
+        }
+        Ok(())
+    }
+
+    /// the number of columns, of the first row when the names are not known
+    fn width(&self) -> usize {
+        match self.columns.is_empty() {
+            true => self.rows.first().map_or(0, Vec::len),
+            false => self.columns.len(),
+        }
+    }
+
+    /// each row as an object keyed by the column names, in the column order,
+    /// or as an array when the names are not known
+    fn records(&self) -> impl Iterator<Item = Json> + '_ {
+        self.rows.iter().map(|row| match self.columns.is_empty() {
+            true => Json::Array(row.clone()),
+            false => Json::Object(self.columns.iter().cloned().zip(row.iter().cloned()).collect::<Map<_, _>>()),
+        })
+    }
+
+    fn write_table(&self, out: &mut impl Write) -> io::Result<()> {
+        let cells: Vec<Vec<String>> = self.rows.iter().map(|row| row.iter().map(to_text).collect()).collect();
+        let mut widths: Vec<usize> = match self.columns.is_empty() {
+            true => vec![0; self.width()],
+            false => self.columns.iter().map(|column| column.chars().count()).collect(),
+        };
+        for row in cells.iter() {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let line = |row: &[String]| -> String {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            cells.join(" | ").trim_end().to_string()
+        };
+        if !self.columns.is_empty() {
+            writeln!(out, "{}", line(&self.columns))?;
+            let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            writeln!(out, "{}", rule.join("-+-"))?;
+        }
+        for row in cells.iter() {
+            writeln!(out, "{}", line(row))?;
+        }
+        writeln!(out, "({} row(s))", self.rows.len())
+    }
+}
+
+/// a value of the query result as json
+fn to_json(value: &Value) -> Json {
+    match value {
+        Value::Nil => Json::Null,
+        Value::Bool(v) => Json::from(*v),
+        Value::S8(v) => Json::from(*v),
+        Value::S16(v) => Json::from(*v),
+        Value::S32(v) => Json::from(*v),
+        Value::S64(v) => Json::from(*v),
+        Value::U8(v) => Json::from(*v),
+        Value::U16(v) => Json::from(*v),
+        Value::U32(v) => Json::from(*v),
+        Value::U64(v) => Json::from(*v),
+        Value::F32(v) => Json::from(*v),
+        Value::F64(v) => Json::from(*v),
+        Value::Text(v) => Json::from(v.as_str()),
+        // a date as RFC 3339, eg: `2021-09-29T16:14:40+00:00`
+        Value::Utc(v) => Json::from(v.to_rfc3339()),
+        // the other values are written as text
+        other => Json::from(format!("{other:?}")),
+    }
+}
+
+/// a json value as the text of a table cell, null is empty
+fn to_text(value: &Json) -> String {
+    match value {
+        Json::Null => String::new(),
+        Json::String(v) => v.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    fn rows() -> Rows {
+        let mut rows = Rows::new(&["eq_id", "full_name", "parent_eq_id"]);
+        rows.push(vec![json!(1), json!("Root | \"Top\", Inc"), Json::Null]);
+        rows.push(vec![json!(2), json!("Child"), json!(1)]);
+        rows
+    }
+
+    fn written(format: Format) -> String {
+        let mut out = vec![];
+        rows().write(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            written(Format::Csv),
+            "eq_id,full_name,parent_eq_id\n1,\"Root | \"\"Top\"\", Inc\",\n2,Child,1\n"
+        );
+    }
+
+    #[test]
+    fn ndjson_keeps_the_column_order() {
+        assert_eq!(
+            written(Format::Ndjson),
+            "{\"eq_id\":1,\"full_name\":\"Root | \\\"Top\\\", Inc\",\"parent_eq_id\":null}\n\
+             {\"eq_id\":2,\"full_name\":\"Child\",\"parent_eq_id\":1}\n"
+        );
+    }
+
+    #[test]
+    fn json_dates_in_rfc_3339() {
+        let created_date: DateTime<Utc> = "2021-09-29T16:14:40Z".parse().unwrap();
+        let pane = DataPane {
+            row_values: vec![vec![Value::U64(11810572), Value::Utc(created_date), Value::Nil]],
+        };
+        let mut out = vec![];
+        Rows::from_pane(&pane).write(Format::Json, &mut out).unwrap();
+        let records: Json = serde_json::from_slice(&out).unwrap();
+        assert_eq!(records, json!([[11810572, "2021-09-29T16:14:40+00:00", null]]));
+    }
+
+    #[test]
+    fn without_the_column_names() {
+        let pane = DataPane {
+            row_values: vec![vec![Value::U64(1), Value::Text("Root".to_string())]],
+        };
+        let rows = Rows::from_pane(&pane);
+        let written = |format: Format| {
+            let mut out = vec![];
+            rows.write(format, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(written(Format::Table), "1 | Root\n(1 row(s))\n");
+        assert_eq!(written(Format::Csv), "1,Root\n");
+        assert_eq!(written(Format::Ndjson), "[1,\"Root\"]\n");
+        assert_eq!(written(Format::Markdown), "|  |  |\n| --- | --- |\n| 1 | Root |\n");
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            written(Format::Markdown),
+            "| eq_id | full_name | parent_eq_id |\n| --- | --- | --- |\n| 1 | Root \\| \"Top\", Inc |  |\n| 2 | Child | 1 |\n"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use clap::ValueEnum;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Editor, Helper};
+use crate::output::{Format, Rows};
+use crate::session::Session;
+
+const HELP: &str = "\
+End a sql statement with `;`, it can span several lines.
+.tables            list the tables
+.schema [table]    show the columns of the tables
+.reload <path>     apply a newer export or a delta file to the customer table
+.timing on|off     show how long each query takes
+.format <format>   table, json, csv, ndjson or markdown
+.help              show this help
+.quit              exit, as does ctrl-d";
+
+/// Completes the table and column names, and keeps reading lines until the statement ends with `;`
+struct SqlHelper {
+    words: Vec<String>,
+}
+
+impl Completer for SqlHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        // the start of the word under the cursor, after the last char which is not part of a name
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(0);
+        let prefix = line[start..pos].to_lowercase();
+        if prefix.is_empty() {
+            return Ok((pos, vec![]));
+        }
+        let candidates = self
+            .words
+            .iter()
+            .filter(|word| word.starts_with(&prefix))
+            .cloned()
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Validator for SqlHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input().trim();
+        if input.is_empty() || input.starts_with('.') || input.ends_with(';') {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+impl Hinter for SqlHelper {
+    type Hint = String;
+}
+
+impl Highlighter for SqlHelper {}
+
+impl Helper for SqlHelper {}
+
+/// the history is kept in the home directory, or the working directory without one
+fn history_path() -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    home.join(".customer_tree_history")
+}
+
+/// the names of the tables of the session and of their columns
+fn words(session: &Session) -> Vec<String> {
+    let mut words: Vec<String> = session
+        .tables()
+        .flat_map(|table| {
+            std::iter::once(table.name.to_string()).chain(table.columns.iter().map(|column| column.name.to_string()))
+        })
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// Read and run the sql statements and meta-commands until ctrl-d or `.quit`,
+/// the tables are loaded once before.
+pub async fn run(session: &mut Session, mut format: Format) -> anyhow::Result<()> {
+    let mut editor: Editor<SqlHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(SqlHelper { words: words(session) }));
+    let history = history_path();
+    // there is no history on the first run
+    let _ = editor.load_history(&history);
+
+    println!("{HELP}");
+    let mut timing = false;
+    loop {
+        let input = match editor.readline("sql> ") {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(input)?;
+
+        if let Some(meta) = input.strip_prefix('.') {
+            let mut args = meta.split_whitespace();
+            match (args.next(), args.next()) {
+                (Some("quit" | "exit"), _) => break,
+                (Some("help"), _) => println!("{HELP}"),
+                (Some("tables"), _) => {
+                    for table in session.tables() {
+                        println!("{}", table.name);
+                    }
+                }
+                (Some("schema"), name) => {
+                    for table in session.tables().filter(|table| name.is_none_or(|name| name == table.name)) {
+                        let mut rows = Rows::new(&["column", "type"]);
+                        for column in table.columns.iter() {
+                            rows.push(vec![column.name.into(), column.type_name.into()]);
+                        }
+                        println!("{}:", table.name);
+                        rows.write(format, &mut std::io::stdout())?;
+                    }
+                }
+                (Some("reload"), Some(path)) => match session.reload(Path::new(path)) {
+                    Ok(report) => print!("{report}"),
+                    Err(e) => eprintln!("error: {e}"),
+                },
+                (Some("timing"), Some("on")) => timing = true,
+                (Some("timing"), Some("off")) => timing = false,
+                (Some("timing"), None) => println!("timing is {}", if timing { "on" } else { "off" }),
+                (Some("format"), Some(name)) => match Format::from_str(name, true) {
+                    Ok(name) => format = name,
+                    Err(e) => eprintln!("{e}"),
+                },
+                _ => eprintln!("unknown command `.{meta}`, see .help"),
+            }
+            continue;
+        }
+
+        let start = Instant::now();
+        match session.query(input.trim_end_matches(';')).await {
+            Ok(rows) => rows.write(format, &mut std::io::stdout())?,
+            Err(e) => eprintln!("error: {e}"),
+        }
+        if timing {
+            println!("time: {:.3}s", start.elapsed().as_secs_f64());
+        }
+    }
+    editor.save_history(&history)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(line: &str) -> (usize, Vec<String>) {
+        let helper = SqlHelper {
+            words: vec!["eq_id".to_string(), "full_name".to_string()],
+        };
+        let history = DefaultHistory::new();
+        let ctx = rustyline::Context::new(&history);
+        helper.complete(line, line.len(), &ctx).unwrap()
+    }
+
+    #[test]
+    fn complete_the_word_under_the_cursor() {
+        assert_eq!(complete("SELECT FU"), (7, vec!["full_name".to_string()]));
+        assert_eq!(complete("SELECT "), (7, vec![]));
+        // the word starts after a char of several bytes
+        assert_eq!(complete("SELECT 'Zoë'||fu"), (15, vec!["full_name".to_string()]));
+        assert_eq!(complete("SELECT €eq"), (10, vec!["eq_id".to_string()]));
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::path::Path;
+use gauntlet::{Context, DataPane, DataSource};
+use customer::{ChangeReport, Column, Customer, CustomerData, CustomerRecords, DedupeOptions, LoadOptions, TableRows};
+use crate::output::Rows;
+
+/// A table of the session, with its columns for the completion and `.schema`
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: &'static str,
+    pub columns: Vec<Column>,
+}
+
+/// The query context of the loaded customer data.
+/// The `customer` table is the loaded one, with every record of the export,
+/// the `duplicate` table is built once, by the first query which refers to it.
+pub struct Session {
+    ctx: Context,
+    /// the records of the `customer` table, the duplicates are searched in them
+    customers: Vec<Customer>,
+    /// the options the records were loaded with
+    options: LoadOptions,
+    /// the rows of the `customer` table written from the records
+    rows: TableRows,
+    /// the tables registered in the context, with the columns they were built with
+    tables: Vec<TableInfo>,
+    /// the tables which are registered by the first query which refers to them
+    pending: Vec<TableInfo>,
+}
+
+impl Session {
+    pub fn new(data: CustomerData) -> anyhow::Result<Self> {
+        let CustomerData {
+            data_source,
+            customers,
+            options,
+            rows,
+            ..
+        } = data;
+        let mut session = Session {
+            ctx: Context::new(),
+            customers,
+            options,
+            rows,
+            tables: vec![],
+            pending: vec![TableInfo {
+                name: "duplicate",
+                columns: customer::duplicate_table_columns().to_vec(),
+            }],
+        };
+        session.register("customer", customer::customer_table_columns(), data_source)?;
+        Ok(session)
+    }
+
+    /// register the table in the context, with the `columns` it is written with
+    fn register(&mut self, name: &'static str, columns: Vec<Column>, data_source: DataSource) -> anyhow::Result<()> {
+        self.ctx.register_table(name, data_source)?;
+        self.pending.retain(|table| table.name != name);
+        self.tables.retain(|table| table.name != name);
+        self.tables.push(TableInfo { name, columns });
+        Ok(())
+    }
+
+    /// Apply the records of a newer export or a delta file at `path` to the records of the `customer` table.
+    /// When something changed the tables are registered in a new context, with the `customer` table built
+    /// again from its rows, of which only the changed ones are written again, and the `duplicate` table
+    /// built again by the next query which refers to it.
+    pub fn reload(&mut self, path: &Path) -> anyhow::Result<ChangeReport> {
+        let delta = CustomerRecords::new(File::open(path)?, &self.options)?.collect::<Result<Vec<_>, _>>()?;
+        let report = self.rows.apply(&mut self.customers, delta, &self.options);
+        if !report.is_empty() {
+            let data_source = self.rows.data_source()?;
+            self.ctx = Context::new();
+            self.pending.extend(self.tables.drain(..).filter(|table| table.name != "customer"));
+            self.register("customer", customer::customer_table_columns(), data_source)?;
+        }
+        Ok(report)
+    }
+
+    /// the context with the `customer` table, for the queries which do not use the `duplicate` table
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// the tables which can be queried, the registered ones then the ones which are not built yet
+    pub fn tables(&self) -> impl Iterator<Item = &TableInfo> {
+        self.tables.iter().chain(self.pending.iter())
+    }
+
+    /// run a sql query, the `duplicate` table is built first when it is the first query to use it
+    pub async fn sql(&mut self, sql: &str) -> anyhow::Result<DataPane> {
+        // the `duplicate` table is the only one which is built on demand
+        if let Some(index) = self.pending.iter().position(|table| refers_to(sql, table.name)) {
+            let mut clusters = customer::find_duplicates(&self.customers, &DedupeOptions::default());
+            if let Some(masking) = &self.options.masking {
+                clusters = clusters.into_iter().map(|cluster| cluster.masked(masking)).collect();
+            }
+            let table = self.pending.remove(index);
+            self.register(table.name, table.columns, customer::duplicate_data(&clusters)?)?;
+        }
+        Ok(self.ctx.sql(sql).await?)
+    }
+
+    /// run a sql query, the result has no column names as gauntlet does not give them with the rows
+    pub async fn query(&mut self, sql: &str) -> anyhow::Result<Rows> {
+        Ok(Rows::from_pane(&self.sql(sql).await?))
+    }
+}
+
+/// whether `name` is a word of the sql, a match in a string or a comment only builds the table earlier
+fn refers_to(sql: &str, name: &str) -> bool {
+    sql.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .any(|word| word.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_is_referred_to() {
+        assert!(refers_to("SELECT * FROM Duplicate d", "duplicate"));
+        assert!(refers_to("select count(*) from customer join duplicate on", "duplicate"));
+        assert!(!refers_to("SELECT duplicate_eq_id FROM customer", "duplicate"));
+    }
+}